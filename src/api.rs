@@ -0,0 +1,372 @@
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, ACCEPT, USER_AGENT};
+use reqwest::{Response, StatusCode};
+use serde::de::DeserializeOwned;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// How many times a transient failure (5xx, network error, rate limit) is retried
+const MAX_RETRIES: u32 = 5;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+// Wait used for a secondary rate limit that doesn't tell us how long to back off
+const SECONDARY_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+
+// JSON over HTTP for every forge: retries, rate limits and a record of the
+// requests that failed for good. Sources add their own headers and URLs.
+pub struct ApiClient {
+    client: reqwest::Client,
+    api_url: String,
+    headers: HeaderMap,
+    limiter: RateLimiter,
+    failures: Mutex<Vec<String>>,
+}
+
+// Token bucket shared by all in-flight requests. Keeps concurrent workers under
+// GitHub's secondary limits, and lets a single rate-limited response pause
+// everyone until the window resets.
+struct RateLimiter {
+    state: Mutex<BucketState>,
+    capacity: f64,
+    per_second: f64,
+}
+
+struct BucketState {
+    tokens: f64,
+    last_refill: Instant,
+    paused_until: Option<Instant>,
+}
+
+impl RateLimiter {
+    fn new(per_second: f64) -> Self {
+        // Allow a burst of one second's worth of requests
+        let capacity = per_second.max(1.0);
+        RateLimiter {
+            state: Mutex::new(BucketState {
+                tokens: capacity,
+                last_refill: Instant::now(),
+                paused_until: None,
+            }),
+            capacity,
+            per_second,
+        }
+    }
+
+    async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut state = self.state.lock().unwrap();
+                let now = Instant::now();
+
+                match state.paused_until {
+                    Some(until) if until > now => until - now,
+                    _ => {
+                        state.paused_until = None;
+                        let elapsed = now.duration_since(state.last_refill).as_secs_f64();
+                        state.tokens =
+                            (state.tokens + elapsed * self.per_second).min(self.capacity);
+                        state.last_refill = now;
+
+                        if state.tokens >= 1.0 {
+                            state.tokens -= 1.0;
+                            return;
+                        }
+                        Duration::from_secs_f64((1.0 - state.tokens) / self.per_second)
+                    }
+                }
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    fn pause(&self, wait: Duration) {
+        let until = Instant::now() + wait;
+        let mut state = self.state.lock().unwrap();
+        if state.paused_until.is_none_or(|current| current < until) {
+            state.paused_until = Some(until);
+        }
+    }
+}
+
+// Headers sent with every request: the forge's JSON media type, and the
+// token in whichever header the forge reads it from
+pub fn create_headers(accept: &'static str, auth: Option<(HeaderName, String)>) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(USER_AGENT, HeaderValue::from_static("ackamoto-bot"));
+    headers.insert(ACCEPT, HeaderValue::from_static(accept));
+
+    if let Some((name, value)) = auth {
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            headers.insert(name, value);
+        }
+    }
+
+    headers
+}
+
+// GitHub sends `X-RateLimit-*`, GitLab the IETF draft `RateLimit-*`
+fn rate_limit_header(headers: &HeaderMap, name: &str) -> Option<u64> {
+    header_u64(headers, &format!("x-ratelimit-{}", name))
+        .or_else(|| header_u64(headers, &format!("ratelimit-{}", name)))
+}
+
+fn header_u64(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+}
+
+// Time left at `now` until the `X-RateLimit-Reset` epoch, plus a second of
+// slack for clock skew
+fn until_reset(reset: u64, now: SystemTime) -> Duration {
+    let now = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    Duration::from_secs(reset.saturating_sub(now) + 1)
+}
+
+// How long GitHub asks us to wait before the next request, if at all
+fn rate_limit_wait(headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    if let Some(seconds) = header_u64(headers, "retry-after") {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    if rate_limit_header(headers, "remaining") == Some(0) {
+        if let Some(reset) = rate_limit_header(headers, "reset") {
+            return Some(until_reset(reset, now));
+        }
+    }
+
+    None
+}
+
+// A 403 is a rate limit when the headers say so or, for secondary limits
+// that come without them, when the message does ("You have exceeded a
+// secondary rate limit...")
+fn is_rate_limited(status: StatusCode, headers: &HeaderMap, message: &str) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS
+        || (status == StatusCode::FORBIDDEN
+            && (headers.contains_key("retry-after")
+                || rate_limit_header(headers, "remaining") == Some(0)
+                || message.to_lowercase().contains("rate limit")))
+}
+
+impl ApiClient {
+    pub fn new(api_url: &str, headers: HeaderMap, requests_per_second: f64) -> Self {
+        ApiClient {
+            client: reqwest::Client::new(),
+            api_url: api_url.trim_end_matches('/').to_string(),
+            headers,
+            limiter: RateLimiter::new(requests_per_second),
+            failures: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn api_url(&self) -> &str {
+        &self.api_url
+    }
+
+    // GET a URL and decode the JSON body, retrying transient failures with
+    // exponential backoff and waiting out rate limits. Requests that still fail
+    // are recorded so they can be reported at the end of the run.
+    pub async fn get_json<T: DeserializeOwned>(
+        &self,
+        url: &str,
+    ) -> Result<T, Box<dyn std::error::Error>> {
+        self.json_with_retry(url, None).await
+    }
+
+    // Every page of a listing, `url` plus "&page=N" from 1 until a page
+    // comes back with fewer than `page_size` items
+    pub async fn get_all_pages<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        page_size: usize,
+    ) -> Result<Vec<T>, Box<dyn std::error::Error>> {
+        let mut all = Vec::new();
+        for page in 1.. {
+            let items: Vec<T> = self.get_json(&format!("{}&page={}", url, page)).await?;
+            let last = items.len() < page_size;
+            all.extend(items);
+            if last {
+                break;
+            }
+        }
+        Ok(all)
+    }
+
+    // Same as `get_json`, but POSTs a JSON body (used for GraphQL queries)
+    pub async fn post_json<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        body: &serde_json::Value,
+    ) -> Result<T, Box<dyn std::error::Error>> {
+        self.json_with_retry(url, Some(body)).await
+    }
+
+    async fn json_with_retry<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        body: Option<&serde_json::Value>,
+    ) -> Result<T, Box<dyn std::error::Error>> {
+        match self.send_with_retry(url, body).await {
+            Ok(response) => match response.json().await {
+                Ok(value) => Ok(value),
+                Err(e) => Err(self.record_failure(url, e.to_string()).into()),
+            },
+            Err(message) => Err(self.record_failure(url, message).into()),
+        }
+    }
+
+    async fn send_with_retry(
+        &self,
+        url: &str,
+        body: Option<&serde_json::Value>,
+    ) -> Result<Response, String> {
+        let mut backoff = INITIAL_BACKOFF;
+        let mut attempt = 0;
+
+        loop {
+            attempt += 1;
+            self.limiter.acquire().await;
+
+            let request = match body {
+                Some(body) => self.client.post(url).json(body),
+                None => self.client.get(url),
+            };
+
+            let error = match request.headers(self.headers.clone()).send().await {
+                Ok(response) => {
+                    let status = response.status();
+
+                    if status.is_success() {
+                        // Out of quota: hold every worker off until the window resets
+                        // so the next request doesn't fail
+                        let headers = response.headers();
+                        if rate_limit_header(headers, "remaining") == Some(0) {
+                            if let Some(reset) = rate_limit_header(headers, "reset") {
+                                let wait = until_reset(reset, SystemTime::now());
+                                println!(
+                                    "Rate limit exhausted, pausing {}s until reset",
+                                    wait.as_secs()
+                                );
+                                self.limiter.pause(wait);
+                            }
+                        }
+                        return Ok(response);
+                    }
+
+                    let headers = response.headers().clone();
+                    let message = if status == StatusCode::FORBIDDEN {
+                        response.text().await.unwrap_or_default()
+                    } else {
+                        String::new()
+                    };
+
+                    if is_rate_limited(status, &headers, &message) {
+                        if attempt > MAX_RETRIES {
+                            return Err(format!("{} (rate limited)", status));
+                        }
+                        let wait = rate_limit_wait(&headers, SystemTime::now())
+                            .unwrap_or(SECONDARY_RATE_LIMIT_WAIT);
+                        println!("Rate limited on {}, retrying in {}s", url, wait.as_secs());
+                        self.limiter.pause(wait);
+                        continue;
+                    }
+
+                    if !status.is_server_error() {
+                        // 404, 401, 422 etc. won't get better by retrying
+                        return Err(status.to_string());
+                    }
+
+                    status.to_string()
+                }
+                Err(e) => e.to_string(),
+            };
+
+            if attempt > MAX_RETRIES {
+                return Err(error);
+            }
+
+            eprintln!(
+                "Request to {} failed ({}), retrying in {}s",
+                url,
+                error,
+                backoff.as_secs()
+            );
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+
+    pub fn record_failure(&self, url: &str, message: String) -> String {
+        let failure = format!("{}: {}", url, message);
+        self.failures.lock().unwrap().push(failure.clone());
+        failure
+    }
+
+    pub fn failures(&self) -> Vec<String> {
+        self.failures.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn waits_for_retry_after_or_reset() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        assert_eq!(until_reset(1_700_000_030, now), Duration::from_secs(31));
+        // A reset already in the past still leaves the second of slack
+        assert_eq!(until_reset(1_699_999_000, now), Duration::from_secs(1));
+
+        let retry_after = headers(&[("retry-after", "7"), ("x-ratelimit-remaining", "0")]);
+        assert_eq!(
+            rate_limit_wait(&retry_after, now),
+            Some(Duration::from_secs(7))
+        );
+        let exhausted = headers(&[
+            ("x-ratelimit-remaining", "0"),
+            ("x-ratelimit-reset", "1700000059"),
+        ]);
+        assert_eq!(
+            rate_limit_wait(&exhausted, now),
+            Some(Duration::from_secs(60))
+        );
+        let gitlab = headers(&[
+            ("ratelimit-remaining", "0"),
+            ("ratelimit-reset", "1700000009"),
+        ]);
+        assert_eq!(rate_limit_wait(&gitlab, now), Some(Duration::from_secs(10)));
+        let remaining = headers(&[
+            ("x-ratelimit-remaining", "12"),
+            ("x-ratelimit-reset", "1700000059"),
+        ]);
+        assert_eq!(rate_limit_wait(&remaining, now), None);
+    }
+
+    #[test]
+    fn recognises_secondary_rate_limits() {
+        let none = HeaderMap::new();
+        let quota = headers(&[("x-ratelimit-remaining", "4999")]);
+        let secondary = "You have exceeded a secondary rate limit. Please wait a few minutes.";
+        assert!(is_rate_limited(StatusCode::FORBIDDEN, &quota, secondary));
+        assert!(is_rate_limited(StatusCode::TOO_MANY_REQUESTS, &none, ""));
+        assert!(is_rate_limited(
+            StatusCode::FORBIDDEN,
+            &headers(&[("retry-after", "60")]),
+            ""
+        ));
+        // Permission errors aren't worth retrying
+        let denied = "Resource not accessible by integration";
+        assert!(!is_rate_limited(StatusCode::FORBIDDEN, &quota, denied));
+        assert!(!is_rate_limited(StatusCode::NOT_FOUND, &none, secondary));
+    }
+}
@@ -0,0 +1,378 @@
+use chrono::NaiveDate;
+use chrono_tz::Tz;
+use clap::parser::ValueSource;
+use clap::ArgMatches;
+use serde::Deserialize;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::theme::Theme;
+use crate::{Backend, Cli, Command, FetchArgs, Forge, Mode, RenderArgs};
+
+pub const DEFAULT_CONFIG_PATH: &str = "ackamoto.toml";
+
+// Longest `serve --refresh` or `daemon --interval`: a year. Much larger values
+// would overflow the Instant the next cycle is scheduled at.
+const MAX_INTERVAL_SECONDS: u64 = 365 * 24 * 60 * 60;
+
+// Settings from `ackamoto.toml`. Every key is optional; anything given on the
+// command line (or through an environment variable) wins over the file, and
+// the file wins over the built-in defaults.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub data: Option<PathBuf>,
+
+    // fetch
+    pub forge: Option<Forge>,
+    pub repo: Option<String>,
+    pub backend: Option<Backend>,
+    pub api_url: Option<String>,
+    pub pr_limit: Option<usize>,
+    pub pr_limit_without_token: Option<usize>,
+    pub pages: Option<u32>,
+    pub page_delay_ms: Option<u64>,
+    pub concurrency: Option<usize>,
+    pub requests_per_second: Option<f64>,
+    pub snippet_length: Option<usize>,
+    pub exclude_users: Option<Vec<String>>,
+    pub skip_bots: Option<bool>,
+
+    // daemon, in minutes
+    pub interval: Option<u64>,
+
+    // render
+    pub mode: Option<Mode>,
+    pub output_dir: Option<PathBuf>,
+    pub index_days: Option<u32>,
+    pub timezone: Option<Tz>,
+    pub date_format: Option<String>,
+    pub single_file: Option<bool>,
+    pub templates: Option<PathBuf>,
+    #[serde(default)]
+    pub theme: Theme,
+}
+
+impl Config {
+    // `path` is the --config value; without it a missing ackamoto.toml is fine
+    pub fn load(path: Option<&Path>) -> Result<Config, Box<dyn std::error::Error>> {
+        let (path, required) = match path {
+            Some(path) => (path, true),
+            None => (Path::new(DEFAULT_CONFIG_PATH), false),
+        };
+
+        if !required && !path.exists() {
+            return Ok(Config::default());
+        }
+
+        let contents =
+            fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let config = toml::from_str(&contents)
+            .map_err(|e| format!("Invalid config {}: {}", path.display(), e))?;
+        Ok(config)
+    }
+
+    // Fill in every setting the user didn't pass explicitly
+    pub fn apply(&self, cli: &mut Cli, matches: &ArgMatches) {
+        if !is_explicit(matches, "data") {
+            if let Some(data) = &self.data {
+                cli.data = data.clone();
+            }
+        }
+
+        match (&mut cli.command, matches.subcommand()) {
+            (Some(Command::Fetch(args)), Some((_, sub))) => self.apply_fetch(args, sub),
+            (Some(Command::Render(args)), Some((_, sub))) => self.apply_render(args, sub),
+            (Some(Command::Serve(args)), Some((_, sub))) => {
+                self.apply_fetch(&mut args.fetch, sub);
+                self.apply_render(&mut args.render, sub);
+            }
+            (Some(Command::Webhook(args)), Some((_, sub))) => {
+                self.apply_fetch(&mut args.fetch, sub);
+                self.apply_render(&mut args.render, sub);
+            }
+            (Some(Command::Daemon(args)), Some((_, sub))) => {
+                self.apply_fetch(&mut args.fetch, sub);
+                self.apply_render(&mut args.render, sub);
+                if !is_explicit(sub, "interval") {
+                    if let Some(interval) = self.interval {
+                        args.interval = interval;
+                    }
+                }
+            }
+            (None, _) => {
+                self.apply_fetch(&mut cli.fetch, matches);
+                self.apply_render(&mut cli.render, matches);
+            }
+            _ => {}
+        }
+    }
+
+    fn apply_fetch(&self, args: &mut FetchArgs, matches: &ArgMatches) {
+        macro_rules! apply {
+            ($field:ident) => {
+                if !is_explicit(matches, stringify!($field)) {
+                    if let Some(value) = &self.$field {
+                        args.$field = value.clone();
+                    }
+                }
+            };
+        }
+
+        apply!(forge);
+        apply!(repo);
+        apply!(backend);
+        apply!(api_url);
+        apply!(pages);
+        apply!(page_delay_ms);
+        apply!(concurrency);
+        apply!(requests_per_second);
+        apply!(snippet_length);
+        apply!(exclude_users);
+
+        if !is_explicit(matches, "pr_limit") && self.pr_limit.is_some() {
+            args.pr_limit = self.pr_limit;
+        }
+        // Config-only settings
+        if let Some(limit) = self.pr_limit_without_token {
+            args.pr_limit_without_token = limit;
+        }
+        if let Some(skip_bots) = self.skip_bots {
+            args.skip_bots = skip_bots;
+        }
+    }
+
+    fn apply_render(&self, args: &mut RenderArgs, matches: &ArgMatches) {
+        if !is_explicit(matches, "mode") {
+            if let Some(mode) = &self.mode {
+                args.mode = mode.clone();
+            }
+        }
+        if !is_explicit(matches, "output_dir") {
+            if let Some(output_dir) = &self.output_dir {
+                args.output_dir = output_dir.clone();
+            }
+        }
+        if !is_explicit(matches, "index_days") {
+            if let Some(index_days) = self.index_days {
+                args.index_days = index_days;
+            }
+        }
+        if !is_explicit(matches, "timezone") {
+            if let Some(timezone) = self.timezone {
+                args.timezone = timezone;
+            }
+        }
+        if !is_explicit(matches, "date_format") {
+            if let Some(date_format) = &self.date_format {
+                args.date_format = date_format.clone();
+            }
+        }
+        if !is_explicit(matches, "single_file") {
+            if let Some(single_file) = self.single_file {
+                args.single_file = single_file;
+            }
+        }
+        if !is_explicit(matches, "templates") && self.templates.is_some() {
+            args.templates = self.templates.clone();
+        }
+        args.theme = self.theme.clone();
+    }
+}
+
+fn is_explicit(matches: &ArgMatches, id: &str) -> bool {
+    matches!(
+        matches.value_source(id),
+        Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+    )
+}
+
+// Checked once at startup so a bad value fails before any request is made
+pub fn validate_fetch(args: &FetchArgs) -> Result<(), String> {
+    if args.requests_per_second <= 0.0 || !args.requests_per_second.is_finite() {
+        return Err(format!(
+            "requests_per_second must be greater than zero, got {}",
+            args.requests_per_second
+        ));
+    }
+    if !(1..=100).contains(&args.concurrency) {
+        return Err(format!(
+            "concurrency must be between 1 and 100, got {}",
+            args.concurrency
+        ));
+    }
+    if args.pr_limit == Some(0) || args.pr_limit_without_token == 0 {
+        return Err("pr_limit and pr_limit_without_token must be at least 1".to_string());
+    }
+    if args.pages == 0 {
+        return Err("pages must be at least 1".to_string());
+    }
+    if args.snippet_length == 0 {
+        return Err("snippet_length must be at least 1".to_string());
+    }
+    if !args.repo.contains('/') && args.mbox.is_none() {
+        return Err(format!("repo must be owner/name, got {}", args.repo));
+    }
+    if args.forge != Forge::Github
+        && args.api_url == crate::github::DEFAULT_API_URL
+        && args.fixtures.is_none()
+        && args.mbox.is_none()
+    {
+        return Err("api_url must point at the Gitea or GitLab instance".to_string());
+    }
+    Ok(())
+}
+
+// `name` is the option the minutes came from, for the error message
+pub fn validate_minutes(name: &str, minutes: u64) -> Result<Duration, String> {
+    match minutes.checked_mul(60) {
+        Some(seconds) if (1..=MAX_INTERVAL_SECONDS).contains(&seconds) => {
+            Ok(Duration::from_secs(seconds))
+        }
+        _ => Err(format!(
+            "{} must be between 1 and {} minutes, got {}",
+            name,
+            MAX_INTERVAL_SECONDS / 60,
+            minutes
+        )),
+    }
+}
+
+pub fn validate_render(args: &RenderArgs) -> Result<(), String> {
+    if args.index_days == 0 {
+        return Err("index_days must be at least 1".to_string());
+    }
+    // chrono panics on a format it can't apply, so try it on a date first;
+    // time fields like %H have nothing to show for a day
+    let mut sample = String::new();
+    if write!(sample, "{}", NaiveDate::MIN.format(&args.date_format)).is_err() {
+        return Err(format!(
+            "date_format must be a strftime format using date fields only, got {:?}",
+            args.date_format
+        ));
+    }
+    args.theme.validate()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_cli;
+
+    const CONFIG: &str = r#"
+        data = "file.json"
+        repo = "file/repo"
+        api_url = "https://file.example.com"
+        pages = 2
+        interval = 30
+        mode = "nack"
+        index_days = 7
+        pr_limit_without_token = 10
+    "#;
+
+    fn configured(config: &str, args: &[&str]) -> Cli {
+        let (mut cli, matches) = parse_cli(args).unwrap();
+        toml::from_str::<Config>(config)
+            .unwrap()
+            .apply(&mut cli, &matches);
+        cli
+    }
+
+    fn fetch_args(args: &[&str]) -> FetchArgs {
+        match parse_cli(args).unwrap().0.command {
+            Some(Command::Fetch(args)) => args,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn command_line_wins_over_file_over_defaults() {
+        let cli = configured(
+            CONFIG,
+            &[
+                "ackamoto",
+                "--data",
+                "cli.json",
+                "daemon",
+                "--pages",
+                "3",
+                "--interval",
+                "15",
+            ],
+        );
+        assert_eq!(cli.data, Path::new("cli.json"));
+        let Some(Command::Daemon(args)) = cli.command else {
+            unreachable!()
+        };
+        assert_eq!(args.interval, 15);
+        assert_eq!(args.fetch.pages, 3);
+        assert_eq!(args.fetch.repo, "file/repo");
+        assert_eq!(args.fetch.pr_limit_without_token, 10);
+        assert_eq!(args.fetch.concurrency, 8);
+        assert_eq!(args.render.mode, Mode::Nack);
+        assert_eq!(args.render.index_days, 7);
+
+        // Without a subcommand the top-level options are filled in the same way
+        let cli = configured(CONFIG, &["ackamoto", "--mode", "ack"]);
+        assert_eq!(cli.data, Path::new("file.json"));
+        assert_eq!(cli.render.mode, Mode::Ack);
+        assert_eq!(cli.fetch.pages, 2);
+
+        // Environment variables count as given on the command line
+        std::env::set_var("ACKAMOTO_API_URL", "https://env.example.com");
+        let cli = configured(CONFIG, &["ackamoto", "fetch"]);
+        std::env::remove_var("ACKAMOTO_API_URL");
+        let Some(Command::Fetch(args)) = cli.command else {
+            unreachable!()
+        };
+        assert_eq!(args.api_url, "https://env.example.com");
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        for config in [
+            "reop = \"bitcoin/bitcoin\"",
+            "[theme]\nfooterr = \"x\"",
+            "[theme.ack]\nlogo_darkk = \"logo.svg\"",
+        ] {
+            assert!(toml::from_str::<Config>(config).is_err(), "{}", config);
+        }
+        assert!(Config::load(Some(Path::new("does-not-exist.toml"))).is_err());
+    }
+
+    #[test]
+    fn bad_values_are_rejected() {
+        assert!(validate_fetch(&fetch_args(&["ackamoto", "fetch"])).is_ok());
+        for args in [
+            &["ackamoto", "fetch", "--concurrency", "0"][..],
+            &["ackamoto", "fetch", "--requests-per-second", "NaN"],
+            &["ackamoto", "fetch", "--pr-limit", "0"],
+            &["ackamoto", "fetch", "--pages", "0"],
+            &["ackamoto", "fetch", "--repo", "bitcoin"],
+            &["ackamoto", "fetch", "--forge", "gitea"],
+        ] {
+            assert!(validate_fetch(&fetch_args(args)).is_err(), "{:?}", args);
+        }
+
+        let render_args = |args: &[&str]| match parse_cli(args).unwrap().0.command {
+            Some(Command::Render(args)) => args,
+            _ => unreachable!(),
+        };
+        assert!(validate_render(&render_args(&["ackamoto", "render"])).is_ok());
+        for args in [
+            &["ackamoto", "render", "--index-days", "0"][..],
+            &["ackamoto", "render", "--date-format", "%H:%M"],
+        ] {
+            assert!(validate_render(&render_args(args)).is_err(), "{:?}", args);
+        }
+
+        assert_eq!(
+            validate_minutes("interval", 120),
+            Ok(Duration::from_secs(7200))
+        );
+        assert!(validate_minutes("interval", 0).is_err());
+        assert!(validate_minutes("refresh", u64::MAX).is_err());
+    }
+}
@@ -0,0 +1,97 @@
+use chrono::Utc;
+use std::path::Path;
+use std::time::Instant;
+
+use crate::config::validate_minutes;
+use crate::{fetch, render, FetchArgs, RenderArgs};
+
+#[derive(clap::Args)]
+pub struct DaemonArgs {
+    /// Minutes between the start of one refresh and the next
+    #[arg(long, value_name = "MINUTES", default_value_t = 120)]
+    pub interval: u64,
+    #[command(flatten)]
+    pub fetch: FetchArgs,
+    #[command(flatten)]
+    pub render: RenderArgs,
+}
+
+fn log(message: &str) {
+    println!(
+        "[{}] {}",
+        Utc::now().format("%Y-%m-%d %H:%M:%S UTC"),
+        message
+    );
+}
+
+// A long-running replacement for the cron job: fetch and render every
+// `interval` minutes. The data file carries state from one cycle (and one
+// process) to the next, and every file is written atomically, so the site
+// can be served straight from the output directory while this runs. A failed
+// cycle leaves the last good site in place.
+pub async fn run(args: &DaemonArgs, data: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let interval = validate_minutes("interval", args.interval)?;
+
+    log(&format!(
+        "Refreshing {} every {} minutes into {}",
+        args.fetch.repo,
+        args.interval,
+        args.render.output_dir.display()
+    ));
+
+    // Ctrl-C takes effect between cycles, so files are never left half-updated
+    let (stop_tx, mut stop_rx) = tokio::sync::oneshot::channel();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            let _ = stop_tx.send(());
+        }
+    });
+
+    let mut cycle = 0;
+    let mut consecutive_failures = 0;
+    loop {
+        cycle += 1;
+        let started = Instant::now();
+        log(&format!("Cycle {} started", cycle));
+
+        let result = match fetch(&args.fetch, data).await {
+            Ok(added) => render(&args.render, data).map(|()| added),
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(added) => {
+                consecutive_failures = 0;
+                log(&format!(
+                    "Cycle {} finished in {:.1}s: {} new entries",
+                    cycle,
+                    started.elapsed().as_secs_f64(),
+                    added
+                ));
+            }
+            Err(e) => {
+                consecutive_failures += 1;
+                log(&format!(
+                    "Cycle {} failed after {:.1}s ({} in a row): {}",
+                    cycle,
+                    started.elapsed().as_secs_f64(),
+                    consecutive_failures,
+                    e
+                ));
+            }
+        }
+
+        let next = interval.saturating_sub(started.elapsed());
+        log(&format!(
+            "Next cycle in {} minutes",
+            next.as_secs().div_ceil(60)
+        ));
+        tokio::select! {
+            _ = tokio::time::sleep(next) => {}
+            _ = &mut stop_rx => {
+                log("Stopping");
+                return Ok(());
+            }
+        }
+    }
+}
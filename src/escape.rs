@@ -0,0 +1,135 @@
+// The one place text from PRs, comments and accounts is made safe to put into
+// generated pages and feeds. Templates apply `escape_html` to every value
+// automatically, `safe_url` is the filter used on every href and
+// `json_for_script` covers data embedded for scripts.
+// Comment snippets are the exception: they're rendered from Markdown and
+// sanitized in `markdown.rs` instead.
+
+use serde::Serialize;
+
+// Schemes a link may use; anything else (javascript:, data:, vbscript: ...)
+// is replaced. mid: links come from mailing-list archives without a web view.
+const ALLOWED_SCHEMES: &[&str] = &["http", "https", "mailto", "mid"];
+
+// Escape for HTML text and quoted attributes (single or double), and for XML
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// JSON to embed in a <script> element. "</script>" or "<!--" inside a string
+// would end or confuse the element, so every "<", ">" and "&" is written as
+// a \u escape, along with the separators JavaScript treats as line breaks.
+pub fn json_for_script(value: &impl Serialize) -> Result<String, serde_json::Error> {
+    Ok(serde_json::to_string(value)?
+        .replace('<', "\\u003c")
+        .replace('>', "\\u003e")
+        .replace('&', "\\u0026")
+        .replace('\u{2028}', "\\u2028")
+        .replace('\u{2029}', "\\u2029"))
+}
+
+// The URL unchanged if it's relative or uses an allowed scheme, otherwise "#".
+// Browsers ignore whitespace and control characters inside a scheme
+// ("java\tscript:"), so those are dropped before checking it. The result
+// still needs escaping.
+pub fn safe_url(url: &str) -> String {
+    let scheme_part: String = url
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace() && !c.is_control())
+        .take_while(|c| !matches!(c, '/' | '?' | '#'))
+        .collect();
+
+    match scheme_part.split_once(':') {
+        None => url.to_string(),
+        Some((scheme, _))
+            if ALLOWED_SCHEMES
+                .iter()
+                .any(|allowed| scheme.eq_ignore_ascii_case(allowed)) =>
+        {
+            url.to_string()
+        }
+        Some(_) => "#".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_markup_and_quotes() {
+        assert_eq!(
+            escape_html(r#"<script>alert("x")</script> & 'y'"#),
+            "&lt;script&gt;alert(&quot;x&quot;)&lt;/script&gt; &amp; &#39;y&#39;"
+        );
+    }
+
+    #[test]
+    fn escapes_already_escaped_text() {
+        // Input that looks escaped is escaped again rather than trusted
+        assert_eq!(escape_html("&lt;b&gt;"), "&amp;lt;b&amp;gt;");
+    }
+
+    #[test]
+    fn leaves_plain_text_alone() {
+        assert_eq!(
+            escape_html("wallet: fix fee bump (#28000)"),
+            "wallet: fix fee bump (#28000)"
+        );
+    }
+
+    #[test]
+    fn json_cannot_close_the_script_element() {
+        let json =
+            json_for_script(&["</script><script>alert(1)</script>", "<!--", "a & b"]).unwrap();
+        assert!(!json.contains('<'));
+        assert!(!json.contains('>'));
+        assert!(!json.contains('&'));
+        let parsed: Vec<String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed[0], "</script><script>alert(1)</script>");
+    }
+
+    #[test]
+    fn allows_web_mail_and_relative_urls() {
+        for url in [
+            "https://github.com/bitcoin/bitcoin/pull/1#issuecomment-2",
+            "http://example.com",
+            "HTTPS://EXAMPLE.COM",
+            "mailto:someone@example.com",
+            "mid:abc@example.com",
+            "pr/28000.html",
+            "../feed.xml",
+            "#top",
+            "/path?a=b:c",
+        ] {
+            assert_eq!(safe_url(url), url);
+        }
+    }
+
+    #[test]
+    fn rejects_script_and_data_urls() {
+        for url in [
+            "javascript:alert(1)",
+            "JavaScript:alert(1)",
+            " javascript:alert(1)",
+            "java\tscript:alert(1)",
+            "java\nscript:alert(1)",
+            "\u{0}javascript:alert(1)",
+            "data:text/html,<script>alert(1)</script>",
+            "vbscript:msgbox(1)",
+        ] {
+            assert_eq!(safe_url(url), "#", "{:?}", url);
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::header::AUTHORIZATION;
+use serde::Deserialize;
+
+use crate::api::{create_headers, ApiClient};
+use crate::source::Source;
+use crate::{Comment, Head, Label, Milestone, PullRequest, User};
+
+// Gitea caps `limit` at 50 by default
+const PAGE_SIZE: usize = 50;
+
+#[derive(Debug, Deserialize)]
+struct GiteaPullRequest {
+    number: u32,
+    title: String,
+    html_url: String,
+    state: String,
+    merged_at: Option<DateTime<Utc>>,
+    // Only returned by newer Forgejo releases
+    #[serde(default)]
+    draft: bool,
+    #[serde(default)]
+    labels: Vec<Label>,
+    user: Option<GiteaUser>,
+    milestone: Option<Milestone>,
+    head: Option<Head>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GiteaComment {
+    #[serde(default)]
+    body: String,
+    // Comments have `created_at`, reviews `submitted_at`
+    #[serde(alias = "submitted_at")]
+    created_at: Option<DateTime<Utc>>,
+    html_url: String,
+    user: Option<GiteaUser>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GiteaUser {
+    login: String,
+    // Only returned by newer Gitea/Forgejo releases
+    #[serde(default)]
+    html_url: Option<String>,
+}
+
+// Gitea and Forgejo (e.g. Codeberg) expose the same /api/v1 endpoints, with
+// comment and review JSON close enough to GitHub's to map one to one
+pub struct GiteaSource {
+    client: ApiClient,
+    base_url: String,
+    repo: String,
+}
+
+impl GiteaSource {
+    // `base_url` is the instance root, e.g. https://codeberg.org
+    pub fn new(
+        base_url: &str,
+        repo: &str,
+        token: Option<String>,
+        requests_per_second: f64,
+    ) -> Self {
+        let base_url = base_url.trim_end_matches('/');
+        let auth = token.map(|token| (AUTHORIZATION, format!("token {}", token)));
+        GiteaSource {
+            client: ApiClient::new(
+                &format!("{}/api/v1", base_url),
+                create_headers("application/json", auth),
+                requests_per_second,
+            ),
+            base_url: base_url.to_string(),
+            repo: repo.to_string(),
+        }
+    }
+
+    fn to_user(&self, user: GiteaUser) -> User {
+        let html_url = user
+            .html_url
+            .unwrap_or_else(|| format!("{}/{}", self.base_url, user.login));
+        User {
+            login: user.login,
+            html_url,
+        }
+    }
+
+    fn to_comment(&self, comment: GiteaComment) -> Option<Comment> {
+        Some(Comment {
+            body: comment.body,
+            created_at: comment.created_at?,
+            html_url: comment.html_url,
+            user: self.to_user(comment.user?),
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Source for GiteaSource {
+    async fn list_pull_requests(
+        &self,
+        limit: usize,
+    ) -> Result<Vec<PullRequest>, Box<dyn std::error::Error>> {
+        let mut all_prs = Vec::new();
+        let mut page = 1;
+
+        while all_prs.len() < limit {
+            let url = format!(
+                "{}/repos/{}/pulls?state=all&limit={}&page={}",
+                self.client.api_url(),
+                self.repo,
+                PAGE_SIZE,
+                page
+            );
+
+            let prs: Vec<GiteaPullRequest> = match self.client.get_json(&url).await {
+                Ok(prs) => prs,
+                Err(e) if all_prs.is_empty() => return Err(e),
+                Err(e) => {
+                    eprintln!("Failed to fetch PRs page {}: {}", page, e);
+                    break;
+                }
+            };
+            if prs.is_empty() {
+                break;
+            }
+
+            all_prs.extend(prs.into_iter().map(|pr| PullRequest {
+                number: pr.number,
+                title: pr.title,
+                html_url: pr.html_url,
+                state: pr.state,
+                merged_at: pr.merged_at,
+                draft: pr.draft,
+                labels: pr.labels,
+                user: pr.user.map(|user| self.to_user(user)),
+                milestone: pr.milestone,
+                head: pr.head,
+            }));
+            page += 1;
+        }
+
+        all_prs.truncate(limit);
+        Ok(all_prs)
+    }
+
+    async fn list_comments(
+        &self,
+        pr_number: u32,
+    ) -> Result<Vec<Comment>, Box<dyn std::error::Error>> {
+        let url = format!(
+            "{}/repos/{}/issues/{}/comments",
+            self.client.api_url(),
+            self.repo,
+            pr_number
+        );
+
+        // Unlike reviews, issue comments aren't paged: one request has them all
+        let comments: Vec<GiteaComment> = self.client.get_json(&url).await?;
+        Ok(comments
+            .into_iter()
+            .filter_map(|comment| self.to_comment(comment))
+            .collect())
+    }
+
+    async fn list_reviews(
+        &self,
+        pr_number: u32,
+    ) -> Result<Vec<Comment>, Box<dyn std::error::Error>> {
+        let url = format!(
+            "{}/repos/{}/pulls/{}/reviews?limit={}",
+            self.client.api_url(),
+            self.repo,
+            pr_number,
+            PAGE_SIZE
+        );
+
+        let reviews: Vec<GiteaComment> = self.client.get_all_pages(&url, PAGE_SIZE).await?;
+        Ok(reviews
+            .into_iter()
+            .filter_map(|review| self.to_comment(review))
+            .collect())
+    }
+
+    fn failures(&self) -> Vec<String> {
+        self.client.failures()
+    }
+}
@@ -0,0 +1,193 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::header::AUTHORIZATION;
+use serde::Deserialize;
+use std::time::Duration;
+
+use crate::api::{create_headers, ApiClient};
+use crate::source::Source;
+use crate::{Comment, PullRequest, User};
+
+pub const DEFAULT_API_URL: &str = "https://api.github.com";
+// The most GitHub returns per page
+const PAGE_SIZE: usize = 100;
+
+// A client for the REST and GraphQL APIs at `api_url`
+pub fn client(api_url: &str, token: Option<String>, requests_per_second: f64) -> ApiClient {
+    let auth = token.map(|token| (AUTHORIZATION, format!("Bearer {}", token)));
+    ApiClient::new(
+        api_url,
+        create_headers("application/vnd.github.v3+json", auth),
+        requests_per_second,
+    )
+}
+
+// `pages` pages of 100 PRs at most, pausing `page_delay` between them
+pub async fn fetch_pull_requests(
+    github: &ApiClient,
+    repo: &str,
+    pages: u32,
+    page_delay: Duration,
+) -> Result<Vec<PullRequest>, Box<dyn std::error::Error>> {
+    let mut all_prs = Vec::new();
+    let mut page = 1;
+
+    loop {
+        let url = format!(
+            "{}/repos/{}/pulls?state=all&per_page={}&page={}",
+            github.api_url(),
+            repo,
+            PAGE_SIZE,
+            page
+        );
+
+        let prs: Vec<PullRequest> = match github.get_json(&url).await {
+            Ok(prs) => prs,
+            // Without the first page there's nothing to show
+            Err(e) if all_prs.is_empty() => return Err(e),
+            Err(e) => {
+                eprintln!("Failed to fetch PRs page {}: {}", page, e);
+                break;
+            }
+        };
+        if prs.is_empty() {
+            break;
+        }
+
+        all_prs.extend(prs);
+
+        if page >= pages {
+            break;
+        }
+
+        page += 1;
+        tokio::time::sleep(page_delay).await;
+    }
+
+    Ok(all_prs)
+}
+
+pub async fn fetch_comments_for_pr(
+    github: &ApiClient,
+    repo: &str,
+    pr_number: u32,
+) -> Result<Vec<Comment>, Box<dyn std::error::Error>> {
+    let url = format!(
+        "{}/repos/{}/issues/{}/comments?per_page={}",
+        github.api_url(),
+        repo,
+        pr_number,
+        PAGE_SIZE
+    );
+
+    github.get_all_pages(&url, PAGE_SIZE).await
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Review {
+    body: Option<String>,
+    submitted_at: Option<DateTime<Utc>>,
+    html_url: String,
+    user: Option<User>,
+}
+
+impl Review {
+    // Pending reviews have no submission date, deleted accounts have no user
+    pub fn into_comment(self) -> Option<Comment> {
+        Some(Comment {
+            body: self.body.unwrap_or_default(),
+            created_at: self.submitted_at?,
+            html_url: self.html_url,
+            user: self.user?,
+        })
+    }
+}
+
+// Review bodies carry ACKs just like issue comments ("ACK abc123" submitted as
+// an approval), so they're returned in the same shape.
+pub async fn fetch_reviews_for_pr(
+    github: &ApiClient,
+    repo: &str,
+    pr_number: u32,
+) -> Result<Vec<Comment>, Box<dyn std::error::Error>> {
+    let url = format!(
+        "{}/repos/{}/pulls/{}/reviews?per_page={}",
+        github.api_url(),
+        repo,
+        pr_number,
+        PAGE_SIZE
+    );
+
+    let reviews: Vec<Review> = github.get_all_pages(&url, PAGE_SIZE).await?;
+    Ok(reviews
+        .into_iter()
+        .filter_map(Review::into_comment)
+        .collect())
+}
+
+// Live REST implementation: one listing for the PRs, then one request per PR
+// for comments and one for reviews
+pub struct GitHubSource {
+    github: ApiClient,
+    repo: String,
+    pages: u32,
+    page_delay: Duration,
+}
+
+impl GitHubSource {
+    pub fn new(github: ApiClient, repo: &str, pages: u32, page_delay: Duration) -> Self {
+        GitHubSource {
+            github,
+            repo: repo.to_string(),
+            pages,
+            page_delay,
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Source for GitHubSource {
+    async fn list_pull_requests(
+        &self,
+        limit: usize,
+    ) -> Result<Vec<PullRequest>, Box<dyn std::error::Error>> {
+        // Only as many listing pages as the limit needs
+        let needed = limit.div_ceil(PAGE_SIZE).min(self.pages as usize) as u32;
+        let mut prs =
+            fetch_pull_requests(&self.github, &self.repo, needed, self.page_delay).await?;
+        prs.truncate(limit);
+        Ok(prs)
+    }
+
+    async fn list_comments(
+        &self,
+        pr_number: u32,
+    ) -> Result<Vec<Comment>, Box<dyn std::error::Error>> {
+        fetch_comments_for_pr(&self.github, &self.repo, pr_number).await
+    }
+
+    async fn list_reviews(
+        &self,
+        pr_number: u32,
+    ) -> Result<Vec<Comment>, Box<dyn std::error::Error>> {
+        fetch_reviews_for_pr(&self.github, &self.repo, pr_number).await
+    }
+
+    fn failures(&self) -> Vec<String> {
+        self.github.failures()
+    }
+}
+
+// Web host matching an API root, used for links in the rendered pages:
+// api.github.com -> github.com, https://ghe.example.com/api/v3 -> https://ghe.example.com.
+// Anything else (e.g. a local mock server) is assumed to serve both.
+pub fn web_url_for(api_url: &str) -> String {
+    let api_url = api_url.trim_end_matches('/');
+    if api_url == DEFAULT_API_URL {
+        return "https://github.com".to_string();
+    }
+    api_url
+        .strip_suffix("/api/v3")
+        .unwrap_or(api_url)
+        .to_string()
+}
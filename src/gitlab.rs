@@ -0,0 +1,197 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::header::HeaderName;
+use serde::Deserialize;
+
+use crate::api::{create_headers, ApiClient};
+use crate::source::Source;
+use crate::{Comment, Head, Label, Milestone, PullRequest, User};
+
+const PAGE_SIZE: usize = 100;
+
+#[derive(Debug, Deserialize)]
+struct MergeRequest {
+    iid: u32,
+    title: String,
+    web_url: String,
+    // opened, closed, merged or locked
+    state: String,
+    merged_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    draft: bool,
+    // Label names only; colours need a separate request per label
+    #[serde(default)]
+    labels: Vec<String>,
+    author: Option<Author>,
+    milestone: Option<Milestone>,
+    sha: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Note {
+    id: u64,
+    body: String,
+    created_at: DateTime<Utc>,
+    author: Author,
+    // Commits pushed, labels changed etc.
+    #[serde(default)]
+    system: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct Author {
+    username: String,
+    web_url: String,
+}
+
+// Merge requests stand in for PRs (numbered by their per-project `iid`) and
+// MR notes for comments. GitLab approvals carry no text, so an ACK only shows
+// up when it's written as a note.
+pub struct GitLabSource {
+    client: ApiClient,
+    base_url: String,
+    repo: String,
+    // URL-encoded `group/project` path, usable as the project id
+    project: String,
+}
+
+impl GitLabSource {
+    // `base_url` is the instance root, e.g. https://gitlab.com
+    pub fn new(
+        base_url: &str,
+        repo: &str,
+        token: Option<String>,
+        requests_per_second: f64,
+    ) -> Self {
+        let base_url = base_url.trim_end_matches('/');
+        let auth = token.map(|token| (HeaderName::from_static("private-token"), token));
+        GitLabSource {
+            client: ApiClient::new(
+                &format!("{}/api/v4", base_url),
+                create_headers("application/json", auth),
+                requests_per_second,
+            ),
+            base_url: base_url.to_string(),
+            repo: repo.to_string(),
+            project: repo.replace('/', "%2F"),
+        }
+    }
+
+    fn merge_request_url(&self, iid: u32) -> String {
+        format!(
+            "{}/projects/{}/merge_requests/{}",
+            self.client.api_url(),
+            self.project,
+            iid
+        )
+    }
+}
+
+#[async_trait(?Send)]
+impl Source for GitLabSource {
+    async fn list_pull_requests(
+        &self,
+        limit: usize,
+    ) -> Result<Vec<PullRequest>, Box<dyn std::error::Error>> {
+        let mut all_prs = Vec::new();
+        let mut page = 1;
+
+        while all_prs.len() < limit {
+            let url = format!(
+                "{}/projects/{}/merge_requests?state=all&order_by=created_at&sort=desc&per_page={}&page={}",
+                self.client.api_url(),
+                self.project,
+                PAGE_SIZE,
+                page
+            );
+
+            let mrs: Vec<MergeRequest> = match self.client.get_json(&url).await {
+                Ok(mrs) => mrs,
+                Err(e) if all_prs.is_empty() => return Err(e),
+                Err(e) => {
+                    eprintln!("Failed to fetch merge requests page {}: {}", page, e);
+                    break;
+                }
+            };
+            if mrs.is_empty() {
+                break;
+            }
+
+            all_prs.extend(mrs.into_iter().map(|mr| {
+                PullRequest {
+                    number: mr.iid,
+                    title: mr.title,
+                    html_url: mr.web_url,
+                    state: match mr.state.as_str() {
+                        "opened" | "locked" => "open".to_string(),
+                        _ => "closed".to_string(),
+                    },
+                    merged_at: mr.merged_at,
+                    draft: mr.draft,
+                    labels: mr
+                        .labels
+                        .into_iter()
+                        .map(|name| Label {
+                            name,
+                            color: String::new(),
+                        })
+                        .collect(),
+                    user: mr.author.map(|author| User {
+                        login: author.username,
+                        html_url: author.web_url,
+                    }),
+                    milestone: mr.milestone,
+                    head: mr.sha.map(|sha| Head { sha }),
+                }
+            }));
+            page += 1;
+        }
+
+        all_prs.truncate(limit);
+        Ok(all_prs)
+    }
+
+    async fn list_comments(
+        &self,
+        pr_number: u32,
+    ) -> Result<Vec<Comment>, Box<dyn std::error::Error>> {
+        let url = format!(
+            "{}/notes?sort=asc&order_by=created_at&per_page={}",
+            self.merge_request_url(pr_number),
+            PAGE_SIZE
+        );
+
+        let notes: Vec<Note> = self.client.get_all_pages(&url, PAGE_SIZE).await?;
+
+        // Notes have no URL of their own; they're anchors on the MR page
+        let web_url = format!(
+            "{}/{}/-/merge_requests/{}",
+            self.base_url, self.repo, pr_number
+        );
+
+        Ok(notes
+            .into_iter()
+            .filter(|note| !note.system)
+            .map(|note| Comment {
+                body: note.body,
+                created_at: note.created_at,
+                html_url: format!("{}#note_{}", web_url, note.id),
+                user: User {
+                    login: note.author.username,
+                    html_url: note.author.web_url,
+                },
+            })
+            .collect())
+    }
+
+    async fn list_reviews(
+        &self,
+        _pr_number: u32,
+    ) -> Result<Vec<Comment>, Box<dyn std::error::Error>> {
+        Ok(Vec::new())
+    }
+
+    fn failures(&self) -> Vec<String> {
+        self.client.failures()
+    }
+}
@@ -0,0 +1,446 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::api::ApiClient;
+use crate::source::Source;
+use crate::{Comment, Head, Label, Milestone, PullRequest, User};
+
+// PRs per query. Each PR pulls up to 100 comments and 100 reviews, so larger
+// pages risk hitting GitHub's node limit and query timeouts.
+const PAGE_SIZE: usize = 25;
+
+// Same ordering as the REST `pulls?state=all` listing (newest first), with the
+// first 100 comments and reviews of each PR inline. PRs with more are finished
+// with COMMENTS_QUERY and REVIEWS_QUERY.
+const PULL_REQUESTS_QUERY: &str = r#"
+query($owner: String!, $name: String!, $first: Int!, $after: String) {
+  repository(owner: $owner, name: $name) {
+    pullRequests(first: $first, after: $after, orderBy: {field: CREATED_AT, direction: DESC}) {
+      pageInfo { hasNextPage endCursor }
+      nodes {
+        number
+        title
+        url
+        state
+        mergedAt
+        isDraft
+        author { login url }
+        labels(first: 20) { nodes { name color } }
+        milestone { title }
+        headRefOid
+        comments(first: 100) {
+          pageInfo { hasNextPage endCursor }
+          nodes { body createdAt url author { login url } }
+        }
+        reviews(first: 100) {
+          pageInfo { hasNextPage endCursor }
+          nodes { body submittedAt url author { login url } }
+        }
+      }
+    }
+  }
+}
+"#;
+
+const COMMENTS_QUERY: &str = r#"
+query($owner: String!, $name: String!, $number: Int!, $after: String) {
+  repository(owner: $owner, name: $name) {
+    pullRequest(number: $number) {
+      comments(first: 100, after: $after) {
+        pageInfo { hasNextPage endCursor }
+        nodes { body createdAt url author { login url } }
+      }
+    }
+  }
+}
+"#;
+
+const REVIEWS_QUERY: &str = r#"
+query($owner: String!, $name: String!, $number: Int!, $after: String) {
+  repository(owner: $owner, name: $name) {
+    pullRequest(number: $number) {
+      reviews(first: 100, after: $after) {
+        pageInfo { hasNextPage endCursor }
+        nodes { body submittedAt url author { login url } }
+      }
+    }
+  }
+}
+"#;
+
+#[derive(Debug, Deserialize)]
+struct GraphQlResponse<T> {
+    data: Option<T>,
+    #[serde(default)]
+    errors: Vec<GraphQlError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphQlError {
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Data {
+    repository: Option<Repository>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Repository {
+    pull_requests: Connection<PullRequestNode>,
+}
+
+// What COMMENTS_QUERY and REVIEWS_QUERY return; only one side is asked for
+#[derive(Debug, Deserialize)]
+struct DiscussionData {
+    repository: Option<DiscussionRepository>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DiscussionRepository {
+    pull_request: Option<DiscussionNode>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DiscussionNode {
+    comments: Option<Connection<CommentNode>>,
+    reviews: Option<Connection<CommentNode>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Connection<T> {
+    #[serde(default)]
+    page_info: Option<PageInfo>,
+    nodes: Vec<T>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PageInfo {
+    has_next_page: bool,
+    end_cursor: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PullRequestNode {
+    number: u32,
+    title: String,
+    url: String,
+    // OPEN, CLOSED or MERGED
+    state: String,
+    merged_at: Option<DateTime<Utc>>,
+    is_draft: bool,
+    author: Option<Author>,
+    labels: Option<Connection<Label>>,
+    milestone: Option<Milestone>,
+    head_ref_oid: Option<String>,
+    comments: Connection<CommentNode>,
+    reviews: Connection<CommentNode>,
+}
+
+// Issue comments and reviews share a shape; reviews just use `submittedAt`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CommentNode {
+    body: String,
+    #[serde(alias = "submittedAt")]
+    created_at: Option<DateTime<Utc>>,
+    url: String,
+    author: Option<Author>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Author {
+    login: String,
+    url: String,
+}
+
+impl CommentNode {
+    fn into_comment(self) -> Option<Comment> {
+        // Pending reviews have no date, deleted accounts have no author
+        let author = self.author?;
+        Some(Comment {
+            body: self.body,
+            created_at: self.created_at?,
+            html_url: self.url,
+            user: User {
+                login: author.login,
+                html_url: author.url,
+            },
+        })
+    }
+}
+
+type Discussion = (Vec<Comment>, Vec<Comment>);
+
+fn error_message(errors: &[GraphQlError]) -> String {
+    errors
+        .iter()
+        .map(|error| error.message.as_str())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+fn next_cursor(page_info: Option<PageInfo>) -> Option<String> {
+    match page_info {
+        Some(PageInfo {
+            has_next_page: true,
+            end_cursor,
+        }) => end_cursor,
+        _ => None,
+    }
+}
+
+// The rest of one PR's comments or reviews (`query` picks which), from
+// `cursor` onwards
+async fn fetch_remaining(
+    github: &ApiClient,
+    graphql_url: &str,
+    (owner, name): (&str, &str),
+    number: u32,
+    query: &str,
+    mut cursor: String,
+) -> Result<Vec<CommentNode>, Box<dyn std::error::Error>> {
+    let mut nodes = Vec::new();
+
+    loop {
+        let body = json!({
+            "query": query,
+            "variables": {
+                "owner": owner,
+                "name": name,
+                "number": number,
+                "after": cursor,
+            },
+        });
+
+        let response: GraphQlResponse<DiscussionData> =
+            github.post_json(graphql_url, &body).await?;
+        if !response.errors.is_empty() {
+            return Err(github
+                .record_failure(graphql_url, error_message(&response.errors))
+                .into());
+        }
+
+        let Some(connection) = response
+            .data
+            .and_then(|data| data.repository)
+            .and_then(|repository| repository.pull_request)
+            .and_then(|pr| pr.comments.or(pr.reviews))
+        else {
+            let message = format!("GraphQL response did not contain PR {}", number);
+            return Err(github.record_failure(graphql_url, message).into());
+        };
+
+        nodes.extend(connection.nodes);
+        match next_cursor(connection.page_info) {
+            Some(end_cursor) => cursor = end_cursor,
+            None => return Ok(nodes),
+        }
+    }
+}
+
+// All of a PR's comments or reviews, starting from the first page the listing
+// returned. If the later pages fail the failure is recorded and the first page
+// is kept, so the run still reports what it found.
+async fn complete_discussion(
+    github: &ApiClient,
+    graphql_url: &str,
+    repo: (&str, &str),
+    number: u32,
+    query: &str,
+    first_page: Connection<CommentNode>,
+) -> Vec<Comment> {
+    let mut nodes = first_page.nodes;
+    if let Some(cursor) = next_cursor(first_page.page_info) {
+        match fetch_remaining(github, graphql_url, repo, number, query, cursor).await {
+            Ok(more) => nodes.extend(more),
+            Err(e) => eprintln!("PR {} is only partly fetched: {}", number, e),
+        }
+    }
+    nodes
+        .into_iter()
+        .filter_map(CommentNode::into_comment)
+        .collect()
+}
+
+// GitHub Enterprise serves GraphQL from /api/graphql next to the /api/v3
+// REST root; github.com and mock servers serve it from <root>/graphql
+fn graphql_url(api_url: &str) -> String {
+    match api_url.strip_suffix("/api/v3") {
+        Some(host) => format!("{}/api/graphql", host),
+        None => format!("{}/graphql", api_url),
+    }
+}
+
+// GraphQL counterpart of `fetch_pull_requests` + `fetch_comments_for_pr` +
+// `fetch_reviews_for_pr`: returns up to `limit` PRs with their comments and
+// reviews already attached, in a handful of requests instead of one per PR.
+async fn fetch_pull_requests_with_discussion(
+    github: &ApiClient,
+    repo: &str,
+    limit: usize,
+) -> Result<Vec<(PullRequest, Discussion)>, Box<dyn std::error::Error>> {
+    let (owner, name) = repo
+        .split_once('/')
+        .ok_or_else(|| format!("Repository must be owner/name, got {}", repo))?;
+    let graphql_url = graphql_url(github.api_url());
+    let mut results = Vec::new();
+    let mut cursor: Option<String> = None;
+
+    while results.len() < limit {
+        let body = json!({
+            "query": PULL_REQUESTS_QUERY,
+            "variables": {
+                "owner": owner,
+                "name": name,
+                "first": PAGE_SIZE.min(limit - results.len()),
+                "after": cursor,
+            },
+        });
+
+        let response: GraphQlResponse<Data> = match github.post_json(&graphql_url, &body).await {
+            Ok(response) => response,
+            // Without the first page there's nothing to show
+            Err(e) if results.is_empty() => return Err(e),
+            Err(e) => {
+                eprintln!("Failed to fetch PRs page after {:?}: {}", cursor, e);
+                break;
+            }
+        };
+
+        if !response.errors.is_empty() {
+            let failure = github.record_failure(&graphql_url, error_message(&response.errors));
+            if results.is_empty() {
+                return Err(failure.into());
+            }
+            eprintln!("GraphQL query failed: {}", failure);
+            break;
+        }
+
+        let Some(repository) = response.data.and_then(|data| data.repository) else {
+            return Err("GraphQL response did not contain the repository".into());
+        };
+        let connection = repository.pull_requests;
+
+        for node in connection.nodes {
+            let repo = (owner, name);
+            let comments = complete_discussion(
+                github,
+                &graphql_url,
+                repo,
+                node.number,
+                COMMENTS_QUERY,
+                node.comments,
+            )
+            .await;
+            let reviews = complete_discussion(
+                github,
+                &graphql_url,
+                repo,
+                node.number,
+                REVIEWS_QUERY,
+                node.reviews,
+            )
+            .await;
+
+            let pr = PullRequest {
+                number: node.number,
+                title: node.title,
+                html_url: node.url,
+                state: match node.state.as_str() {
+                    "OPEN" => "open".to_string(),
+                    _ => "closed".to_string(),
+                },
+                merged_at: node.merged_at,
+                draft: node.is_draft,
+                labels: node.labels.map(|labels| labels.nodes).unwrap_or_default(),
+                user: node.author.map(|author| User {
+                    login: author.login,
+                    html_url: author.url,
+                }),
+                milestone: node.milestone,
+                head: node.head_ref_oid.map(|sha| Head { sha }),
+            };
+            results.push((pr, (comments, reviews)));
+        }
+
+        match next_cursor(connection.page_info) {
+            Some(end_cursor) => cursor = Some(end_cursor),
+            None => break,
+        }
+    }
+
+    Ok(results)
+}
+
+// Fetches everything in `list_pull_requests` and serves comments and reviews
+// from what that query returned, so the per-PR calls cost no requests
+pub struct GraphQlSource {
+    github: ApiClient,
+    repo: String,
+    discussions: Mutex<HashMap<u32, Discussion>>,
+}
+
+impl GraphQlSource {
+    pub fn new(github: ApiClient, repo: &str) -> Self {
+        GraphQlSource {
+            github,
+            repo: repo.to_string(),
+            discussions: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Source for GraphQlSource {
+    async fn list_pull_requests(
+        &self,
+        limit: usize,
+    ) -> Result<Vec<PullRequest>, Box<dyn std::error::Error>> {
+        let results = fetch_pull_requests_with_discussion(&self.github, &self.repo, limit).await?;
+        let mut discussions = self.discussions.lock().unwrap();
+
+        Ok(results
+            .into_iter()
+            .map(|(pr, discussion)| {
+                discussions.insert(pr.number, discussion);
+                pr
+            })
+            .collect())
+    }
+
+    async fn list_comments(
+        &self,
+        pr_number: u32,
+    ) -> Result<Vec<Comment>, Box<dyn std::error::Error>> {
+        let mut discussions = self.discussions.lock().unwrap();
+        match discussions.get_mut(&pr_number) {
+            Some((comments, _)) => Ok(std::mem::take(comments)),
+            None => Err(format!("PR {} was not part of the GraphQL listing", pr_number).into()),
+        }
+    }
+
+    async fn list_reviews(
+        &self,
+        pr_number: u32,
+    ) -> Result<Vec<Comment>, Box<dyn std::error::Error>> {
+        let mut discussions = self.discussions.lock().unwrap();
+        match discussions.get_mut(&pr_number) {
+            Some((_, reviews)) => Ok(std::mem::take(reviews)),
+            None => Err(format!("PR {} was not part of the GraphQL listing", pr_number).into()),
+        }
+    }
+
+    fn failures(&self) -> Vec<String> {
+        self.github.failures()
+    }
+}
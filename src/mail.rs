@@ -0,0 +1,323 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mail_parser::mailbox::{maildir, mbox};
+use mail_parser::MessageParser;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use crate::source::Source;
+use crate::{strip_quoted_lines, Comment, PullRequest, User};
+
+struct MailMessage {
+    id: String,
+    parent: Option<String>,
+    subject: String,
+    sender: User,
+    date: DateTime<Utc>,
+    body: String,
+}
+
+// Review over email: each thread (a patch series and its replies) stands in
+// for a PR, titled by the subject of its first message and numbered from its
+// Message-ID, and every reply is a comment. Messages link to `archive_url` with `{message_id}` substituted, or
+// to a `mid:` URI when no archive is configured.
+pub struct MailSource {
+    threads: Vec<(PullRequest, Vec<Comment>)>,
+}
+
+fn read_mbox(path: &Path) -> Result<Vec<Vec<u8>>, Box<dyn std::error::Error>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut messages = Vec::new();
+    for message in mbox::MessageIterator::new(reader) {
+        messages.push(message?.unwrap_contents());
+    }
+    Ok(messages)
+}
+
+fn read_maildir(path: &Path) -> Result<Vec<Vec<u8>>, Box<dyn std::error::Error>> {
+    let mut messages = Vec::new();
+    for message in maildir::MessageIterator::new(path)? {
+        messages.push(message?.unwrap_contents());
+    }
+    Ok(messages)
+}
+
+fn parse_message(raw: &[u8]) -> Option<MailMessage> {
+    let message = MessageParser::default().parse(raw)?;
+
+    let id = message.message_id()?.to_string();
+    // In-Reply-To names the direct parent; fall back to the last References entry
+    let parent = message
+        .in_reply_to()
+        .as_text()
+        .or_else(|| {
+            message
+                .references()
+                .as_text_list()?
+                .last()
+                .map(|id| id.as_ref())
+        })
+        .map(str::to_string);
+
+    let from = message.from()?.first()?;
+    let address = from.address.as_deref().unwrap_or_default().to_string();
+    let login = from
+        .name
+        .as_deref()
+        .filter(|name| !name.trim().is_empty())
+        .unwrap_or(&address)
+        .to_string();
+
+    let date = DateTime::from_timestamp(message.date()?.to_timestamp(), 0)?;
+
+    Some(MailMessage {
+        id,
+        parent,
+        subject: message.subject().unwrap_or("(no subject)").to_string(),
+        sender: User {
+            login,
+            html_url: format!("mailto:{}", address),
+        },
+        date,
+        body: strip_quoted_lines(&message.body_text(0).unwrap_or_default()),
+    })
+}
+
+// A thread's number, from its first message's Message-ID so it doesn't move
+// when messages are added to or dropped from the archive
+fn thread_number(id: &str) -> u32 {
+    let digest = Sha256::digest(id.as_bytes());
+    let number = u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]) >> 1;
+    number.max(1)
+}
+
+// Walk up In-Reply-To until we leave the archive; replies whose parent we
+// never saw start their own thread. In a reply cycle (only seen in malformed
+// archives) the cycle's oldest message is the root, so the whole cycle still
+// lands in one thread.
+fn root_of<'a>(messages: &'a HashMap<String, MailMessage>, id: &'a str) -> &'a str {
+    let mut path = vec![id];
+    let mut current = id;
+    while let Some(parent) = messages[current].parent.as_deref() {
+        if !messages.contains_key(parent) {
+            break;
+        }
+        if let Some(start) = path.iter().position(|&seen| seen == parent) {
+            return path[start..]
+                .iter()
+                .min_by_key(|&&id| (messages[id].date, id))
+                .unwrap();
+        }
+        path.push(parent);
+        current = parent;
+    }
+    current
+}
+
+impl MailSource {
+    // `path` is an mbox file or a maildir directory (with cur/ and new/)
+    pub fn open(
+        path: &Path,
+        archive_url: Option<&str>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let raw_messages = if path.is_dir() {
+            read_maildir(path)?
+        } else {
+            read_mbox(path)?
+        };
+
+        let messages: HashMap<String, MailMessage> = raw_messages
+            .iter()
+            .filter_map(|raw| parse_message(raw))
+            .map(|message| (message.id.clone(), message))
+            .collect();
+
+        let link = |id: &str| match archive_url {
+            Some(template) => template.replace("{message_id}", id),
+            None => format!("mid:{}", id),
+        };
+
+        let mut replies: HashMap<String, Vec<&MailMessage>> = HashMap::new();
+        for message in messages.values() {
+            let root = root_of(&messages, &message.id);
+            if root != message.id {
+                replies.entry(root.to_string()).or_default().push(message);
+            }
+        }
+
+        // Oldest first, so on the rare clash of numbers the older thread keeps its own
+        let mut roots: Vec<&MailMessage> = messages
+            .values()
+            .filter(|message| root_of(&messages, &message.id) == message.id)
+            .collect();
+        roots.sort_by(|a, b| a.date.cmp(&b.date).then_with(|| a.id.cmp(&b.id)));
+
+        let mut taken = HashSet::new();
+        let mut threads: Vec<(PullRequest, Vec<Comment>)> = roots
+            .iter()
+            .map(|root| {
+                let mut number = thread_number(&root.id);
+                while !taken.insert(number) {
+                    number = number % (u32::MAX >> 1) + 1;
+                }
+                let pr = PullRequest {
+                    number,
+                    title: root.subject.clone(),
+                    html_url: link(&root.id),
+                    user: Some(root.sender.clone()),
+                    ..PullRequest::default()
+                };
+
+                let mut thread_replies = replies.remove(&root.id).unwrap_or_default();
+                thread_replies.sort_by(|a, b| a.date.cmp(&b.date).then_with(|| a.id.cmp(&b.id)));
+                let comments = thread_replies
+                    .into_iter()
+                    .map(|reply| Comment {
+                        body: reply.body.clone(),
+                        created_at: reply.date,
+                        html_url: link(&reply.id),
+                        user: reply.sender.clone(),
+                    })
+                    .collect();
+
+                (pr, comments)
+            })
+            .collect();
+
+        // Newest threads first, like the PR listings
+        threads.reverse();
+
+        Ok(MailSource { threads })
+    }
+}
+
+#[async_trait(?Send)]
+impl Source for MailSource {
+    async fn list_pull_requests(
+        &self,
+        limit: usize,
+    ) -> Result<Vec<PullRequest>, Box<dyn std::error::Error>> {
+        Ok(self
+            .threads
+            .iter()
+            .take(limit)
+            .map(|(pr, _)| pr.clone())
+            .collect())
+    }
+
+    async fn list_comments(
+        &self,
+        pr_number: u32,
+    ) -> Result<Vec<Comment>, Box<dyn std::error::Error>> {
+        self.threads
+            .iter()
+            .find(|(pr, _)| pr.number == pr_number)
+            .map(|(_, comments)| comments.clone())
+            .ok_or_else(|| format!("No mail thread numbered {}", pr_number).into())
+    }
+
+    // Email has no separate review objects; replies cover everything
+    async fn list_reviews(
+        &self,
+        _pr_number: u32,
+    ) -> Result<Vec<Comment>, Box<dyn std::error::Error>> {
+        Ok(Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(id: &str, headers: &str, day: u32, from: &str, body: &str) -> String {
+        format!(
+            "From {from} Mon Oct  {day} 00:00:00 2026\n\
+             From: {from} <{from}@example.org>\n\
+             Message-ID: <{id}>\n\
+             {headers}\
+             Subject: [PATCH] message {id}\n\
+             Date: Mon, {day} Oct 2026 00:00:00 +0000\n\
+             \n\
+             {body}\n\n"
+        )
+    }
+
+    fn thread<'a>(source: &'a MailSource, title_id: &str) -> &'a (PullRequest, Vec<Comment>) {
+        let title = format!("[PATCH] message {}", title_id);
+        source
+            .threads
+            .iter()
+            .find(|(pr, _)| pr.title == title)
+            .unwrap()
+    }
+
+    fn authors(comments: &[Comment]) -> Vec<&str> {
+        comments.iter().map(|c| c.user.login.as_str()).collect()
+    }
+
+    #[test]
+    fn mbox_messages_are_grouped_into_threads() {
+        // Found by search: two Message-IDs whose thread numbers clash
+        let (older, newer) = ("c2722@example.org", "c34066@example.org");
+        assert_eq!(thread_number(older), thread_number(newer));
+
+        let mbox = [
+            message("root@x", "", 1, "alice", "The patch"),
+            message("ack@x", "In-Reply-To: <root@x>\n", 2, "bob", "ACK"),
+            // No In-Reply-To: the last References entry is the parent
+            message(
+                "refs@x",
+                "References: <root@x> <ack@x>\n",
+                3,
+                "carol",
+                "Tested ACK",
+            ),
+            message("orphan@x", "In-Reply-To: <gone@x>\n", 4, "dave", "NACK"),
+            message("loop-a@x", "In-Reply-To: <loop-b@x>\n", 5, "erin", "a"),
+            message("loop-b@x", "In-Reply-To: <loop-a@x>\n", 6, "frank", "b"),
+            message(older, "", 7, "grace", "first"),
+            message(newer, "", 8, "heidi", "second"),
+        ]
+        .concat();
+
+        let path = std::env::temp_dir().join(format!("ackamoto-mail-{}.mbox", std::process::id()));
+        std::fs::write(&path, mbox).unwrap();
+        let source = MailSource::open(&path, Some("https://lists/{message_id}")).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // One thread per root, newest first
+        let titles: Vec<&str> = source
+            .threads
+            .iter()
+            .map(|(pr, _)| pr.title.as_str())
+            .collect();
+        assert_eq!(
+            titles,
+            [
+                format!("[PATCH] message {}", newer),
+                format!("[PATCH] message {}", older),
+                "[PATCH] message loop-a@x".to_string(),
+                "[PATCH] message orphan@x".to_string(),
+                "[PATCH] message root@x".to_string(),
+            ]
+        );
+
+        let (pr, comments) = thread(&source, "root@x");
+        assert_eq!(pr.number, thread_number("root@x"));
+        assert_eq!(pr.html_url, "https://lists/root@x");
+        assert_eq!(authors(comments), ["bob", "carol"]);
+
+        // A reply to a message outside the archive starts its own thread
+        assert!(thread(&source, "orphan@x").1.is_empty());
+
+        // The oldest message of a reply cycle is its root
+        assert_eq!(authors(&thread(&source, "loop-a@x").1), ["frank"]);
+
+        // On a clash the older thread keeps the number and the newer one moves up
+        assert_eq!(thread(&source, older).0.number, thread_number(older));
+        assert_eq!(thread(&source, newer).0.number, thread_number(older) + 1);
+    }
+}
@@ -1,32 +1,328 @@
-use chrono::{DateTime, Utc};
-use clap::{Parser, ValueEnum};
-use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION, USER_AGENT};
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use minijinja::{context, Value};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
 use std::env;
+use std::ffi::OsString;
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-#[derive(Debug, Clone, ValueEnum)]
+mod api;
+mod config;
+mod daemon;
+mod escape;
+mod gitea;
+mod github;
+mod gitlab;
+mod graphql;
+mod mail;
+mod markdown;
+mod query;
+mod serve;
+mod source;
+mod stats;
+mod store;
+mod templates;
+mod theme;
+mod webhook;
+
+use config::Config;
+use daemon::DaemonArgs;
+use escape::{escape_html, json_for_script, safe_url};
+use gitea::GiteaSource;
+use github::{web_url_for, GitHubSource, DEFAULT_API_URL};
+use gitlab::GitLabSource;
+use graphql::GraphQlSource;
+use mail::MailSource;
+use serve::ServeArgs;
+use source::{collect_pull_requests, FixtureSource, RecordingSource, Source};
+use store::Store;
+use templates::{pr_page_path, DateDisplay, Templates, FONTS};
+use theme::{Site, Theme};
+use webhook::WebhookArgs;
+
+#[derive(Debug, Clone, PartialEq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
 enum Mode {
     Ack,
     Nack,
 }
 
+#[derive(Debug, Clone, PartialEq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Forge {
+    Github,
+    Gitea,
+    Gitlab,
+}
+
+#[derive(Debug, Clone, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Backend {
+    Rest,
+    Graphql,
+}
+
 #[derive(Parser)]
 #[command(name = "ackamoto")]
 #[command(about = "Track Bitcoin Core ACKs and NACKs")]
-struct Args {
+struct Cli {
+    /// Settings file; command-line options override it [default: ackamoto.toml if present]
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
+    /// File where fetched ACKs and NACKs are kept between runs
+    #[arg(long, global = true, value_name = "PATH", default_value = "data/acks.json")]
+    data: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
+    // Without a subcommand, fetch and then render in one go
+    #[command(flatten)]
+    fetch: FetchArgs,
+    #[command(flatten)]
+    render: RenderArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Fetch ACKs and NACKs and merge them into the data file
+    Fetch(FetchArgs),
+    /// Generate the site from the data file without calling any API
+    Render(RenderArgs),
+    /// List stored ACKs and NACKs in the terminal
+    Query(QueryArgs),
+    /// Summarise the stored ACKs and NACKs
+    Stats,
+    /// Serve the site locally, re-rendering and reloading pages when the data changes
+    Serve(ServeArgs),
+    /// Keep running, fetching and rendering on a schedule
+    Daemon(DaemonArgs),
+    /// Receive GitHub webhooks and update the site as comments and reviews arrive
+    Webhook(WebhookArgs),
+}
+
+#[derive(Args, Clone)]
+struct FetchArgs {
+    /// Where the repository is hosted (Gitea also covers Forgejo)
+    #[arg(long, value_enum, default_value_t = Forge::Github)]
+    forge: Forge,
+    /// Repository to track, as owner/name (group/project on GitLab)
+    #[arg(long, default_value = "bitcoin/bitcoin")]
+    repo: String,
+    /// GitHub API used to fetch PRs, comments and reviews
+    #[arg(long, value_enum, default_value_t = Backend::Rest)]
+    backend: Backend,
+    /// GitHub REST API root, e.g. https://ghe.example.com/api/v3 for GitHub Enterprise.
+    /// For Gitea and GitLab, the instance URL, e.g. https://codeberg.org
+    #[arg(long, env = "ACKAMOTO_API_URL", default_value = DEFAULT_API_URL)]
+    api_url: String,
+    /// Replay PRs and comments from a directory recorded with --record instead of calling GitHub
+    #[arg(long, value_name = "DIR", conflicts_with = "record")]
+    fixtures: Option<PathBuf>,
+    /// Track replies in a local mailing-list archive (mbox file or maildir) instead of a forge
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["fixtures", "record"])]
+    mbox: Option<PathBuf>,
+    /// Link for each email, with {message_id} replaced, e.g. https://lore.kernel.org/list/{message_id}/
+    #[arg(long, value_name = "TEMPLATE", requires = "mbox")]
+    mail_archive_url: Option<String>,
+    /// Save every GitHub response into DIR for later replay with --fixtures
+    #[arg(long, value_name = "DIR")]
+    record: Option<PathBuf>,
+    /// Number of recent PRs to scan [default: 250 with a token, 25 without]
+    #[arg(long)]
+    pr_limit: Option<usize>,
+    // PRs to scan without a token unless --pr-limit is given (config only).
+    // One listing page plus comments and reviews for 25 PRs is 51 requests,
+    // under the 60 an hour GitHub allows without a token
+    #[arg(skip = 25usize)]
+    pr_limit_without_token: usize,
+    /// Maximum pages of 100 PRs requested from the GitHub REST listing
+    #[arg(long, default_value_t = 5)]
+    pages: u32,
+    /// Pause between GitHub REST listing pages, in milliseconds
+    #[arg(long, default_value_t = 500)]
+    page_delay_ms: u64,
+    /// Number of PRs whose comments and reviews are fetched at the same time
+    #[arg(long, default_value_t = 8)]
+    concurrency: usize,
+    /// Upper bound on GitHub API requests per second across all workers
+    #[arg(long, default_value_t = 10.0)]
+    requests_per_second: f64,
+    /// Characters of each comment kept as its snippet
+    #[arg(long, default_value_t = 200)]
+    snippet_length: usize,
+    /// Ignore comments by this account (repeatable)
+    #[arg(long = "exclude-user", value_name = "LOGIN", default_values_t = ["bitcoin-core-ci".to_string()])]
+    exclude_users: Vec<String>,
+    // Ignore accounts with "bot" in their name (config only)
+    #[arg(skip = true)]
+    skip_bots: bool,
+}
+
+impl FetchArgs {
+    // What votes are stored under. Mail threads are kept apart from --repo,
+    // by archive name, so they can't pass for its PRs.
+    fn tracked_repo(&self) -> String {
+        match &self.mbox {
+            Some(path) => {
+                let name = path.file_name().unwrap_or(path.as_os_str());
+                format!("mail:{}", name.to_string_lossy())
+            }
+            None => self.repo.clone(),
+        }
+    }
+}
+
+#[derive(Args, Clone)]
+struct RenderArgs {
     #[arg(long, value_enum, default_value_t = Mode::Ack)]
     mode: Mode,
+    /// Directory the site is written to, along with copies of images/ and fonts/
+    #[arg(long, value_name = "DIR", default_value = "site")]
+    output_dir: PathBuf,
+    /// Days of votes shown on index.html; older ones are on the monthly archive pages
+    #[arg(long, value_name = "DAYS", default_value_t = 30)]
+    index_days: u32,
+    /// Timezone used to group votes by day and show dates, e.g. Europe/Berlin
+    #[arg(long, value_name = "TZ", default_value = "UTC")]
+    timezone: Tz,
+    /// strftime format for the dates shown on the pages
+    #[arg(long, value_name = "FORMAT", default_value = "%Y-%m-%d")]
+    date_format: String,
+    /// Write a single self-contained index.html with every vote and its CSS,
+    /// fonts and logos inlined
+    #[arg(long)]
+    single_file: bool,
+    // Only set from the config file's [theme] table
+    #[arg(skip)]
+    theme: Theme,
+    /// Directory of templates overriding the built-in ones (layout.html, index.html,
+    /// error.html, style.css and those in partials/)
+    #[arg(long, value_name = "DIR")]
+    templates: Option<PathBuf>,
 }
 
-#[derive(Debug, Deserialize)]
+impl RenderArgs {
+    fn templates(&self) -> Templates {
+        let dates = DateDisplay {
+            timezone: self.timezone,
+            format: self.date_format.clone(),
+        };
+        Templates::new(self.templates.as_deref(), dates, self.single_file)
+    }
+
+    fn site(&self) -> Site {
+        Site::new(&self.mode, &self.theme)
+    }
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum QueryFormat {
+    Table,
+    Jsonl,
+    Csv,
+}
+
+#[derive(Args)]
+struct QueryArgs {
+    /// Only show ACKs or only NACKs
+    #[arg(long, value_enum)]
+    mode: Option<Mode>,
+    /// Only this vote type, e.g. "Concept ACK" (case-insensitive)
+    #[arg(long = "type", value_name = "TYPE")]
+    ack_type: Option<String>,
+    /// Only votes by this reviewer (case-insensitive)
+    #[arg(long)]
+    reviewer: Option<String>,
+    /// Only votes on this PR number
+    #[arg(long)]
+    pr: Option<u32>,
+    /// Only votes on this repository, as owner/name
+    #[arg(long)]
+    repo: Option<String>,
+    /// Only votes on or after this day (YYYY-MM-DD, UTC)
+    #[arg(long, value_name = "DATE")]
+    since: Option<NaiveDate>,
+    /// Only votes on or before this day (YYYY-MM-DD, UTC)
+    #[arg(long, value_name = "DATE")]
+    until: Option<NaiveDate>,
+    /// Maximum number of rows, newest first
+    #[arg(long)]
+    limit: Option<usize>,
+    #[arg(long, value_enum, default_value_t = QueryFormat::Table)]
+    format: QueryFormat,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 struct PullRequest {
     number: u32,
     title: String,
     html_url: String,
+    // The fields below are missing from mail threads and older recordings.
+    // `state` is "open" or "closed", as GitHub's REST API reports it.
+    #[serde(default)]
+    state: String,
+    #[serde(default)]
+    merged_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    draft: bool,
+    #[serde(default)]
+    labels: Vec<Label>,
+    #[serde(default)]
+    user: Option<User>,
+    #[serde(default)]
+    milestone: Option<Milestone>,
+    #[serde(default)]
+    head: Option<Head>,
+}
+
+impl PullRequest {
+    fn status(&self) -> Option<PrState> {
+        if self.merged_at.is_some() {
+            return Some(PrState::Merged);
+        }
+        match self.state.as_str() {
+            "open" if self.draft => Some(PrState::Draft),
+            "open" => Some(PrState::Open),
+            "closed" => Some(PrState::Closed),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum PrState {
+    Open,
+    Draft,
+    Merged,
+    Closed,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+struct Label {
+    name: String,
+    // Hex colour without the "#", where the forge has one
+    #[serde(default)]
+    color: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct Milestone {
+    title: String,
+}
+
+// The commit a PR currently points at, as REST's `head` object reports it
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct Head {
+    sha: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 struct Comment {
     body: String,
     created_at: DateTime<Utc>,
@@ -34,17 +330,30 @@ struct Comment {
     user: User,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 struct User {
     login: String,
     html_url: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 struct Ack {
+    repo: String,
+    mode: Mode,
     pr_number: u32,
     pr_title: String,
     pr_url: String,
+    // PR metadata as of the latest fetch; absent in data stored before it was tracked
+    #[serde(default)]
+    pr_state: Option<PrState>,
+    #[serde(default)]
+    pr_merged_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pr_labels: Vec<Label>,
+    #[serde(default)]
+    pr_author: Option<String>,
+    #[serde(default)]
+    pr_milestone: Option<String>,
     commenter: String,
     commenter_url: String,
     comment_url: String,
@@ -53,30 +362,16 @@ struct Ack {
     ack_type: String,
 }
 
-fn create_headers(token: Option<String>) -> HeaderMap {
-    let mut headers = HeaderMap::new();
-    headers.insert(USER_AGENT, HeaderValue::from_static("ackamoto-bot"));
-    headers.insert(
-        ACCEPT,
-        HeaderValue::from_static("application/vnd.github.v3+json"),
-    );
-
-    if let Some(token) = token {
-        if let Ok(auth_value) = HeaderValue::from_str(&format!("Bearer {}", token)) {
-            headers.insert(AUTHORIZATION, auth_value);
-        }
-    }
-
-    headers
+// Remove quoted lines (lines starting with ">") from a comment or email body
+fn strip_quoted_lines(body: &str) -> String {
+    body.lines()
+        .filter(|line| !line.trim().starts_with('>'))
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 fn extract_ack_type(body: &str, mode: &Mode) -> Option<String> {
-    // Remove quoted lines (lines starting with ">") from the body
-    let unquoted_body: String = body
-        .lines()
-        .filter(|line| !line.trim().starts_with('>'))
-        .collect::<Vec<_>>()
-        .join("\n");
+    let unquoted_body = strip_quoted_lines(body);
     
     let lower_body = unquoted_body.to_lowercase();
     
@@ -145,6 +440,13 @@ fn extract_ack_type(body: &str, mode: &Mode) -> Option<String> {
             if lower_body.contains("code review ack") {
                 return Some("Code Review ACK".to_string());
             }
+            // Email review trailers
+            if lower_body.contains("tested-by:") {
+                return Some("Tested ACK".to_string());
+            }
+            if lower_body.contains("acked-by:") && !lower_body.contains("nacked-by:") {
+                return Some("ACK".to_string());
+            }
 
             // For regular ACK or reACK, ensure it's a standalone word
             let words: Vec<&str> = lower_body.split_whitespace().collect();
@@ -170,6 +472,10 @@ fn extract_ack_type(body: &str, mode: &Mode) -> Option<String> {
             if lower_body.contains("weak nack") {
                 return Some("Weak NACK".to_string());
             }
+            // Email review trailer
+            if lower_body.contains("nacked-by:") {
+                return Some("NACK".to_string());
+            }
 
             // For regular NACK, ensure it's a standalone word
             let words: Vec<&str> = lower_body.split_whitespace().collect();
@@ -186,601 +492,1139 @@ fn extract_ack_type(body: &str, mode: &Mode) -> Option<String> {
     None
 }
 
+// The start of a comment, without the text it quotes, up to `max_length`
+// characters. A line that doesn't fit is cut short rather than dropped.
 fn truncate_comment(body: &str, max_length: usize) -> String {
-    let lines: Vec<&str> = body.lines().collect();
-    let mut result = String::new();
-
-    for line in lines {
-        if result.len() + line.len() > max_length {
-            result.push_str("...");
-            break;
-        }
-        result.push_str(line);
-        result.push('\n');
+    let body = strip_quoted_lines(body);
+    let body = body.trim();
+    match body.char_indices().nth(max_length) {
+        Some((cut, _)) => format!("{}...", body[..cut].trim_end()),
+        None => body.to_string(),
     }
+}
 
-    result.trim().to_string()
+fn generate_error_html(
+    templates: &Templates,
+    error_message: &str,
+    site: &Site,
+) -> Result<String, Box<dyn std::error::Error>> {
+    templates.render(
+        "error.html",
+        context! {
+            site,
+            root => "",
+            message => error_message,
+        },
+    )
 }
 
-async fn fetch_pull_requests(
-    client: &reqwest::Client,
-    headers: &HeaderMap,
-) -> Result<Vec<PullRequest>, Box<dyn std::error::Error>> {
-    let mut all_prs = Vec::new();
-    let mut page = 1;
+fn format_date(date: &DateTime<Utc>) -> String {
+    date.format("%Y-%m-%d").to_string()
+}
 
-    loop {
-        let url = format!(
-            "https://api.github.com/repos/bitcoin/bitcoin/pulls?state=all&per_page=100&page={}",
-            page
-        );
+// Link target for the ACK type badges: the project's review guidelines
+fn review_guide_url(forge: &Forge, api_url: &str, repo: &str) -> String {
+    match forge {
+        Forge::Github => format!(
+            "{}/{}/blob/master/CONTRIBUTING.md#peer-review",
+            web_url_for(api_url),
+            repo
+        ),
+        Forge::Gitea => format!(
+            "{}/{}/src/branch/master/CONTRIBUTING.md",
+            api_url.trim_end_matches('/'),
+            repo
+        ),
+        Forge::Gitlab => format!(
+            "{}/{}/-/blob/master/CONTRIBUTING.md",
+            api_url.trim_end_matches('/'),
+            repo
+        ),
+    }
+}
 
-        let response = client.get(&url).headers(headers.clone()).send().await?;
+// Links between the index and the monthly archive pages, by "YYYY-MM"
+#[derive(Default, Serialize)]
+struct ArchiveNav {
+    // Every archived month, newest first
+    months: Vec<String>,
+    // The month this page shows, if it's an archive page
+    current: Option<String>,
+    newer: Option<String>,
+    older: Option<String>,
+}
 
-        if !response.status().is_success() {
-            eprintln!("Failed to fetch PRs: {}", response.status());
-            break;
+impl ArchiveNav {
+    fn for_month(months: &[String], index: usize) -> ArchiveNav {
+        ArchiveNav {
+            months: months.to_vec(),
+            current: Some(months[index].clone()),
+            newer: index.checked_sub(1).map(|i| months[i].clone()),
+            older: months.get(index + 1).cloned(),
         }
+    }
+}
 
-        let prs: Vec<PullRequest> = response.json().await?;
-        if prs.is_empty() {
-            break;
-        }
+// `path` is where the page goes in the output directory; links and assets
+// are relative to it so the site works from anywhere
+fn generate_html(
+    templates: &Templates,
+    acks: &[Ack],
+    site: &Site,
+    review_url: &str,
+    path: &str,
+    nav: &ArchiveNav,
+) -> Result<String, Box<dyn std::error::Error>> {
+    // Relative links from pages in subdirectories go back up to the root
+    let root = "../".repeat(path.matches('/').count());
 
-        all_prs.extend(prs);
+    // Group ACKs by day in the display timezone, most recent first
+    let dates = &templates.dates;
+    let mut acks_by_date: BTreeMap<NaiveDate, Vec<&Ack>> = BTreeMap::new();
+    for ack in acks {
+        acks_by_date
+            .entry(dates.day(&ack.date))
+            .or_default()
+            .push(ack);
+    }
+    let date_groups: Vec<_> = acks_by_date
+        .iter()
+        .rev()
+        .map(|(day, acks)| {
+            context! {
+                date => dates.format_day(*day),
+                datetime => day.to_string(),
+                acks,
+            }
+        })
+        .collect();
 
-        // Fetch 5 pages (500 PRs) to stay well under rate limits
-        // 5 pages + 500 comment fetches = ~505 requests (well under 5000/hour limit)
-        if page >= 5 {
-            break;
-        }
+    // What the search script matches on, in the order entries appear
+    let search_data: Vec<_> = acks_by_date
+        .values()
+        .rev()
+        .flatten()
+        .map(|ack| {
+            serde_json::json!({
+                "pr_number": ack.pr_number,
+                "pr_title": ack.pr_title,
+                "commenter": ack.commenter,
+                "ack_type": ack.ack_type,
+            })
+        })
+        .collect();
 
-        page += 1;
-        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-    }
+    templates.render(
+        "index.html",
+        context! {
+            canonical_url => site.page_url(path),
+            description => describe(acks, site, dates),
+            site,
+            root,
+            review_url,
+            updated_at => dates.format_time(&Utc::now()),
+            date_groups,
+            nav,
+            search_data => Value::from_safe_string(json_for_script(&search_data)?),
+            single_file => templates.single_file,
+        },
+    )
+}
 
-    Ok(all_prs)
+// Summary for link previews, e.g. "12 ACKs on 5 Bitcoin Core PRs, latest 2024-03-05"
+fn describe(acks: &[Ack], site: &Site, dates: &DateDisplay) -> String {
+    let Some(latest) = acks.iter().map(|ack| ack.date).max() else {
+        return format!("No {} {}s yet", site.project, site.site_type);
+    };
+    let prs: HashSet<u32> = acks.iter().map(|ack| ack.pr_number).collect();
+    format!(
+        "{} {}{} on {} {} PR{}, latest {}",
+        acks.len(),
+        site.site_type,
+        if acks.len() == 1 { "" } else { "s" },
+        prs.len(),
+        site.project,
+        if prs.len() == 1 { "" } else { "s" },
+        dates.format_day(dates.day(&latest))
+    )
 }
 
-async fn fetch_comments_for_pr(
-    client: &reqwest::Client,
-    headers: &HeaderMap,
-    pr_number: u32,
-) -> Result<Vec<Comment>, Box<dyn std::error::Error>> {
-    let url = format!(
-        "https://api.github.com/repos/bitcoin/bitcoin/issues/{}/comments?per_page=100",
-        pr_number
-    );
+// Every generated page with the time of its newest entry, for search engines
+fn generate_sitemap(site: &Site, pages: &[(String, DateTime<Utc>)]) -> String {
+    let urls = pages
+        .iter()
+        .map(|(path, updated)| {
+            format!(
+                "  <url><loc>{}</loc><lastmod>{}</lastmod></url>\n",
+                escape_html(&site.page_url(path)),
+                updated.format("%Y-%m-%d")
+            )
+        })
+        .collect::<String>();
 
-    let response = client.get(&url).headers(headers.clone()).send().await?;
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+{}</urlset>
+"#,
+        urls
+    )
+}
 
-    if !response.status().is_success() {
-        eprintln!(
-            "Failed to fetch comments for PR {}: {}",
-            pr_number,
-            response.status()
-        );
-        return Ok(Vec::new());
-    }
+// Atom feed of the most recent entries, for readers who'd rather subscribe
+fn generate_feed(acks: &[Ack], site: &Site) -> String {
+    let updated = acks.iter().map(|ack| ack.date).max().unwrap_or_else(Utc::now);
 
-    let comments: Vec<Comment> = response.json().await?;
-    Ok(comments)
-}
+    let entries = acks
+        .iter()
+        .take(50)
+        .map(|ack| {
+            format!(
+                r#"  <entry>
+    <id>{}</id>
+    <title>{} on #{}: {}</title>
+    <link href="{}"/>
+    <updated>{}</updated>
+    <author><name>{}</name><uri>{}</uri></author>
+    <summary>{}</summary>
+  </entry>
+"#,
+                escape_html(&ack.comment_url),
+                escape_html(&ack.ack_type),
+                ack.pr_number,
+                escape_html(&ack.pr_title),
+                escape_html(&safe_url(&ack.comment_url)),
+                ack.date.to_rfc3339(),
+                escape_html(&ack.commenter),
+                escape_html(&safe_url(&ack.commenter_url)),
+                escape_html(&ack.comment_snippet)
+            )
+        })
+        .collect::<String>();
 
-fn generate_error_html(error_message: &str, mode: &Mode) -> String {
-    let (site_name, site_type, _site_title) = match mode {
-        Mode::Ack => ("ackamoto", "ACK", "ACKamoto"),
-        Mode::Nack => ("nackamoto", "NACK", "NACKamoto"),
-    };
-    
     format!(
-        r#"<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta charset="UTF-8">
-    <meta name="viewport" content="width=device-width, initial-scale=1.0">
-    <title>Bitcoin Core {}s - {}.com</title>
-    <link rel="icon" type="image/png" href="images/{}-logo.png">
-    <link href="https://fonts.googleapis.com/css2?family=Roboto:wght@100;400&family=Roboto+Mono:wght@100;400&family=Cormorant+Garamond:wght@300;400&display=swap" rel="stylesheet">
-    <style>
-        :root {{
-            --bg-color: #fff;
-            --text-color: #222;
-        }}
-        
-        @media (prefers-color-scheme: dark) {{
-            :root {{
-                --bg-color: #131313;
-                --text-color: #fff;
-            }}
-        }}
-        
-        body {{
-            font-family: 'Roboto Mono', monospace;
-            font-weight: 400;
-            line-height: 1.2;
-            color: var(--text-color);
-            max-width: 900px;
-            margin: 0 auto;
-            padding: 2rem;
-            background: var(--bg-color);
-            font-size: 1rem;
-            letter-spacing: 0;
-        }}
-        h1 {{
-            font-size: 4rem;
-            font-weight: 400;
-            color: var(--text-color);
-            text-align: center;
-            margin-bottom: 1rem;
-            letter-spacing: 0.01em;
-            font-family: 'Roboto Mono', monospace;
-            font-weight: 400;
-        }}
-        .error-message {{
-            font-size: 1.4rem;
-            color: var(--text-color);
-            text-align: center;
-            margin: 2rem 0;
-            padding: 2rem;
-            border: 2px solid var(--text-color);
-            background: transparent;
-        }}
-    </style>
-</head>
-<body>
-    <h1>Bitcoin Core {}s</h1>
-    <div class="error-message">
-        {}<br><br>
-        The site will automatically retry when GitHub Actions runs every 2 hours.
-    </div>
-</body>
-</html>"#,
-        site_type, site_name, site_name, site_type, error_message
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <id>{}/</id>
+  <title>{} {}s</title>
+  <link href="{}/"/>
+  <link rel="self" href="{}/feed.xml"/>
+  <updated>{}</updated>
+{}</feed>
+"#,
+        escape_html(&site.url),
+        escape_html(&site.project),
+        site.site_type,
+        escape_html(&site.url),
+        escape_html(&site.url),
+        updated.to_rfc3339(),
+        entries
     )
 }
 
-fn format_date(date: &DateTime<Utc>) -> String {
-    date.format("%Y-%m-%d").to_string()
+// Turn the fetched discussion into ACKs and NACKs; every comment is checked
+// for both so one fetch serves both sites
+fn collect_acks(args: &FetchArgs, prs: Vec<(PullRequest, Vec<Comment>)>) -> Vec<Ack> {
+    let mut all_acks = Vec::new();
+    let repo = args.tracked_repo();
+
+    for (pr, comments) in prs {
+        for comment in comments {
+            // Skip bot comments
+            let username_lower = comment.user.login.to_lowercase();
+            if (args.skip_bots && username_lower.contains("bot"))
+                || args
+                    .exclude_users
+                    .iter()
+                    .any(|user| user.eq_ignore_ascii_case(&username_lower))
+            {
+                continue;
+            }
+
+            for mode in [Mode::Ack, Mode::Nack] {
+                if let Some(ack_type) = extract_ack_type(&comment.body, &mode) {
+                    let ack = Ack {
+                        repo: repo.clone(),
+                        mode,
+                        pr_number: pr.number,
+                        pr_title: pr.title.clone(),
+                        pr_url: pr.html_url.clone(),
+                        pr_state: pr.status(),
+                        pr_merged_at: pr.merged_at,
+                        pr_labels: pr.labels.clone(),
+                        pr_author: pr.user.as_ref().map(|user| user.login.clone()),
+                        pr_milestone: pr.milestone.as_ref().map(|m| m.title.clone()),
+                        commenter: comment.user.login.clone(),
+                        commenter_url: comment.user.html_url.clone(),
+                        comment_url: comment.html_url.clone(),
+                        date: comment.created_at,
+                        comment_snippet: truncate_comment(&comment.body, args.snippet_length),
+                        ack_type,
+                    };
+                    all_acks.push(ack);
+                }
+            }
+        }
+    }
+
+    all_acks
 }
 
-fn generate_html(acks: &[Ack], mode: &Mode) -> String {
-    let now = Utc::now();
-    let (site_name, site_type, _site_title) = match mode {
-        Mode::Ack => ("ackamoto", "ACK", "ACKamoto"),
-        Mode::Nack => ("nackamoto", "NACK", "NACKamoto"),
+// Returns how many new entries were added to the data file
+async fn fetch(args: &FetchArgs, data: &Path) -> Result<usize, Box<dyn std::error::Error>> {
+    let token_var = match args.forge {
+        Forge::Github => "GITHUB_TOKEN",
+        Forge::Gitea => "GITEA_TOKEN",
+        Forge::Gitlab => "GITLAB_TOKEN",
     };
+    let token = env::var(token_var).ok();
+
+    // Check if we have a token to determine rate limits
+    let has_token = token.is_some();
     
-    // Calculate date range if we have ACKs
-    let _date_range_text = if !acks.is_empty() {
-        let oldest_date = acks.iter().map(|ack| &ack.date).min().unwrap();
-        let newest_date = acks.iter().map(|ack| &ack.date).max().unwrap();
-        format!(
-            "{} to {}",
-            oldest_date.format("%Y-%m-%d"),
-            newest_date.format("%Y-%m-%d")
-        )
+    let prs_limit = if args.fixtures.is_some() || args.mbox.is_some() {
+        usize::MAX // Replay everything that was recorded or archived
+    } else if let Some(limit) = args.pr_limit {
+        limit
+    } else if has_token {
+        250  // With token, check 250 PRs for both ACKs and NACKs
     } else {
-        // When no ACKs found, show an approximate date range for the PRs we searched
-        let end_date = Utc::now();
-        let start_date = end_date - chrono::Duration::days(7);
-        format!(
-            "{} to {}",
-            start_date.format("%Y-%m-%d"),
-            end_date.format("%Y-%m-%d")
-        )
+        args.pr_limit_without_token  // Without token, check 25 PRs by default
     };
     
-    // If no ACKs, generate a simple empty page
-    if acks.is_empty() {
-        return format!(
-            r#"<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta charset="UTF-8">
-    <meta name="viewport" content="width=device-width, initial-scale=1.0">
-    <title>Bitcoin Core {}s - {}.com</title>
-    <link rel="icon" type="image/png" href="images/{}-logo.png">
-    <link href="https://fonts.googleapis.com/css2?family=Roboto:wght@100;400&family=Roboto+Mono:wght@100;400&family=Cormorant+Garamond:wght@300;400&display=swap" rel="stylesheet">
-    <style>
-        :root {{
-            --bg-color: #fff;
-            --text-color: #222;
-        }}
-        
-        @media (prefers-color-scheme: dark) {{
-            :root {{
-                --bg-color: #131313;
-                --text-color: #fff;
-            }}
-        }}
-        
-        body {{
-            font-family: 'Roboto Mono', monospace;
-            font-weight: 400;
-            line-height: 1.2;
-            color: var(--text-color);
-            max-width: 900px;
-            margin: 0 auto;
-            padding: 2rem;
-            background: var(--bg-color);
-            font-size: 1rem;
-            letter-spacing: 0;
-        }}
-        .title-section {{
-            text-align: center;
-            margin-bottom: 2rem;
-        }}
-        .logo {{
-            height: 16rem;
-            width: auto;
-            display: block;
-            margin: 0 auto;
-        }}
-        .logo-light {{
-            display: block;
-        }}
-        .logo-dark {{
-            display: none;
-        }}
-        @media (prefers-color-scheme: dark) {{
-            .logo-light {{
-                display: none;
-            }}
-            .logo-dark {{
-                display: block;
-            }}
-        }}
-        .last-updated {{
-            color: #888;
-            font-size: 1rem;
-            margin-bottom: 3rem;
-            margin-top: 0;
-            text-align: left;
-            font-family: 'Cormorant Garamond', serif;
-            font-weight: 300;
-            letter-spacing: 0;
-            transform: scaleX(0.85);
-            transform-origin: left;
-        }}
-        .date-range {{
-            color: #888;
-            font-size: 0.9rem;
-            margin-bottom: 3rem;
-            margin-top: 0;
-            text-align: left;
-            font-family: 'Roboto Mono', monospace;
-            font-weight: 400;
-            letter-spacing: 0;
-        }}
-        @media (max-width: 768px) {{
-            body {{
-                padding: 1rem;
-            }}
-            .logo {{
-                height: 8rem;
-            }}
-        }}
-    </style>
-</head>
-<body>
-    <div class="title-section">
-        <img src="images/{}-logo.png" alt="{}" class="logo logo-light">
-        <img src="images/{}-logo-dark.png" alt="{}" class="logo logo-dark">
-    </div>
-    {}
-    <p class="last-updated">Last updated at {} UTC</p>
-</body>
-</html>"#,
-            site_type, site_name, site_name, site_name, _site_title, site_name, _site_title,
-            match mode {
-                Mode::Ack => r#"<p class="last-updated"><a href="https://nackamoto.com" style="color: inherit;">Looking for NACKs? → nackamoto.com</a></p>"#,
-                Mode::Nack => r#"<p class="last-updated"><a href="https://ackamoto.com" style="color: inherit;">Looking for ACKs? → ackamoto.com</a></p>"#,
-            },
-            now.format("%Y-%m-%d %H:%M")
-        );
+    let source: Box<dyn Source> = if let Some(dir) = &args.fixtures {
+        println!("Replaying fixtures from {}", dir.display());
+        Box::new(FixtureSource::new(dir))
+    } else if let Some(path) = &args.mbox {
+        println!("Reading mail archive {}", path.display());
+        Box::new(MailSource::open(path, args.mail_archive_url.as_deref())?)
+    } else {
+        if !has_token {
+            println!("Warning: No {} found. API requests will be limited.", token_var);
+        }
+
+        let live: Box<dyn Source> = match (&args.forge, &args.backend) {
+            (Forge::Gitea, _) => Box::new(GiteaSource::new(
+                &args.api_url,
+                &args.repo,
+                token,
+                args.requests_per_second,
+            )),
+            (Forge::Gitlab, _) => Box::new(GitLabSource::new(
+                &args.api_url,
+                &args.repo,
+                token,
+                args.requests_per_second,
+            )),
+            (Forge::Github, Backend::Rest) => {
+                let github = github::client(&args.api_url, token, args.requests_per_second);
+                Box::new(GitHubSource::new(
+                    github,
+                    &args.repo,
+                    args.pages,
+                    Duration::from_millis(args.page_delay_ms),
+                ))
+            }
+            (Forge::Github, Backend::Graphql) => {
+                if !has_token {
+                    println!("Warning: the GraphQL API requires a GITHUB_TOKEN.");
+                }
+                let github = github::client(&args.api_url, token, args.requests_per_second);
+                Box::new(GraphQlSource::new(github, &args.repo))
+            }
+        };
+
+        match &args.record {
+            Some(dir) => {
+                println!("Recording responses to {}", dir.display());
+                Box::new(RecordingSource::new(live, dir))
+            }
+            None => live,
+        }
+    };
+
+    println!("Fetching pull requests...");
+    let prs = collect_pull_requests(source.as_ref(), prs_limit, args.concurrency).await?;
+    println!("Found {} pull requests", prs.len());
+
+    let acks = collect_acks(args, prs);
+    println!("Found {} ACKs and NACKs", acks.len());
+
+    let mut store = Store::load(data)?;
+    let added = store.merge(acks);
+    // Mailing lists have no CONTRIBUTING.md to link the vote types to
+    if args.mbox.is_none() {
+        store.review_url = review_guide_url(&args.forge, &args.api_url, &args.repo);
     }
+    store.save(data)?;
+    println!("Added {} new entries to {}", added, data.display());
 
-    // Group ACKs by date
-    let mut acks_by_date: std::collections::HashMap<String, Vec<&Ack>> = std::collections::HashMap::new();
-    for ack in acks {
-        let date_key = format_date(&ack.date);
-        acks_by_date.entry(date_key).or_insert_with(Vec::new).push(ack);
+    let failures = source.failures();
+    if !failures.is_empty() {
+        println!("{} request(s) failed after retries:", failures.len());
+        for failure in &failures {
+            println!("  {}", failure);
+        }
     }
-    
-    // Filter out empty date groups and sort dates chronologically (most recent first)
-    let mut sorted_dates: Vec<_> = acks_by_date.iter()
-        .filter(|(_, acks)| !acks.is_empty())
-        .map(|(date, _)| date.clone())
-        .collect();
-    sorted_dates.sort_by(|a, b| b.cmp(a)); // Sort dates in reverse order (newest first)
 
-    format!(
-        r#"<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta charset="UTF-8">
-    <meta name="viewport" content="width=device-width, initial-scale=1.0">
-    <title>Bitcoin Core {}s - {}.com</title>
-    <link rel="icon" type="image/png" href="images/{}-logo.png">
-    <link href="https://fonts.googleapis.com/css2?family=Roboto:wght@100;400&family=Roboto+Mono:wght@100;400&family=Cormorant+Garamond:wght@300;400&display=swap" rel="stylesheet">
-    <style>
-        :root {{
-            --bg-color: #fff;
-            --text-color: #222;
-            --border-color: #e5e5e5;
-        }}
-        
-        @media (prefers-color-scheme: dark) {{
-            :root {{
-                --bg-color: #131313;
-                --text-color: #fff;
-                --border-color: #333;
-            }}
-        }}
-        
-        body {{
-            font-family: 'Roboto Mono', monospace;
-            font-weight: 400;
-            line-height: 1.2;
-            color: var(--text-color);
-            max-width: 900px;
-            margin: 0 auto;
-            padding: 2rem;
-            background: var(--bg-color);
-            font-size: 1rem;
-            letter-spacing: 0;
-        }}
-        .title-section {{
-            text-align: center;
-            margin-bottom: 2rem;
-        }}
-        .logo {{
-            height: 16rem;
-            width: auto;
-            display: block;
-            margin: 0 auto;
-        }}
-        .logo-light {{
-            display: block;
-        }}
-        .logo-dark {{
-            display: none;
-        }}
-        @media (prefers-color-scheme: dark) {{
-            .logo-light {{
-                display: none;
-            }}
-            .logo-dark {{
-                display: block;
-            }}
-        }}
-        .date-header {{
-            font-family: 'Cormorant Garamond', serif;
-            font-weight: 300;
-            font-size: 1rem;
-            color: #888;
-            text-align: left;
-            margin: 3rem 0 4rem 0;
-            letter-spacing: 0;
-            transform: scaleX(0.85);
-            transform-origin: left;
-            transform-origin: left;
-        }}
-        .last-updated {{
-            color: #888;
-            font-size: 1rem;
-            margin-bottom: 3rem;
-            margin-top: 0;
-            text-align: left;
-            font-family: 'Cormorant Garamond', serif;
-            font-weight: 300;
-            letter-spacing: 0;
-            transform: scaleX(0.85);
-            transform-origin: left;
-        }}
-        .date-range {{
-            color: #888;
-            font-size: 1rem;
-            margin-bottom: 3rem;
-            margin-top: 0;
-            text-align: left;
-            font-family: 'Cormorant Garamond', serif;
-            font-weight: 300;
-            letter-spacing: 0;
-            transform: scaleX(0.85);
-            transform-origin: left;
-        }}
-        .acks-container {{
-            margin-top: 1rem;
-        }}
-        .ack-entry {{
-            display: flex;
-            flex-direction: column;
-            gap: 1rem;
-            margin-bottom: 4rem;
-        }}
-        a {{
-            color: var(--text-color);
-            text-decoration: underline;
-            text-underline-offset: 0.3em;
-        }}
-        a:hover {{
-            color: var(--text-color);
-        }}
-        .pr-title {{
-            word-wrap: break-word;
-            line-height: 1rem;
-        }}
-        a.pr-number {{
-            font-size: 1rem;
-            font-weight: 400;
-        }}
-        .pr-title, .ack-type {{
-            font-size: 1rem;
-            font-weight: 400;
-        }}
-        a.commenter {{
-            font-size: 1rem;
-            font-weight: 400;
-        }}
-        .ack-type {{
-            display: inline-block;
-            padding: 0.5rem;
-            border: 2px solid var(--text-color);
-            width: fit-content;
-            text-decoration: none;
-        }}
-        @media (max-width: 768px) {{
-            body {{
-                padding: 1rem;
-            }}
-            .logo {{
-                height: 8rem;
-            }}
-        }}
-    </style>
-</head>
-<body>
-    <div class="title-section">
-        <img src="images/{}-logo.png" alt="{}" class="logo logo-light">
-        <img src="images/{}-logo-dark.png" alt="{}" class="logo logo-dark">
-    </div>
-    {}
-    <p class="last-updated">Last updated at {}</p>
-"#,
-        site_type, site_name, site_name, site_name, _site_title, site_name, _site_title,
-        match mode {
-            Mode::Ack => r#"<p class="last-updated"><a href="https://nackamoto.com" style="color: inherit;">Looking for NACKs? → nackamoto.com</a></p>"#,
-            Mode::Nack => r#"<p class="last-updated"><a href="https://ackamoto.com" style="color: inherit;">Looking for ACKs? → ackamoto.com</a></p>"#,
-        },
-        now.format("%Y-%m-%d %H:%M UTC")
-    ) + &sorted_dates
+    Ok(added)
+}
+
+// Copy a directory tree, e.g. the logos into the output directory
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+// Write to a temporary file next to `path` and rename it into place, so a
+// reader (or a web server) never sees a half-written file
+fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp = path.with_file_name(format!(".{}.tmp", file_name));
+    fs::write(&temp, contents)?;
+    fs::rename(&temp, path)
+}
+
+fn write_output(
+    output_dir: &Path,
+    name: &str,
+    contents: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let path = output_dir.join(name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_atomic(&path, contents.as_bytes()).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(())
+}
+
+fn render(args: &RenderArgs, data: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let store = Store::load(data)?;
+    let acks = store.acks_for(&args.mode);
+    let out = &args.output_dir;
+    let templates = args.templates();
+    let dates = &templates.dates;
+    let site = args.site();
+
+    println!("Found {} ACKs total", acks.len());
+
+    if args.single_file {
+        // No archive or PR pages to link to, so everything goes on the one page
+        write_output(
+            out,
+            "index.html",
+            &generate_html(
+                &templates,
+                &acks,
+                &site,
+                &store.review_url,
+                "index.html",
+                &ArchiveNav::default(),
+            )?,
+        )?;
+        println!("Generated self-contained index.html in {}", out.display());
+        return Ok(());
+    }
+
+    // Older entries move to one archive page per month, so the index stays
+    // a manageable size however long the history grows
+    let mut acks_by_month: BTreeMap<String, Vec<Ack>> = BTreeMap::new();
+    for ack in &acks {
+        acks_by_month
+            .entry(dates.day(&ack.date).format("%Y-%m").to_string())
+            .or_default()
+            .push(ack.clone());
+    }
+    let months: Vec<String> = acks_by_month.keys().rev().cloned().collect();
+
+    let cutoff = dates.day(&Utc::now()) - chrono::Duration::days(args.index_days as i64 - 1);
+    let recent: Vec<Ack> = acks
         .iter()
-        .map(|date| {
-            let date_acks = &acks_by_date[date];
-            let date_header = format!(
-                r#"    
-    <h2 class="date-header">{}</h2>
-    
-    <div class="acks-container">
-"#,
-                date.to_uppercase()
+        .filter(|ack| dates.day(&ack.date) >= cutoff)
+        .cloned()
+        .collect();
+    let index_nav = ArchiveNav {
+        months: months.clone(),
+        ..ArchiveNav::default()
+    };
+
+    // Each page written, with its newest entry, for the sitemap
+    let newest = |acks: &[Ack]| {
+        acks.iter()
+            .map(|ack| ack.date)
+            .max()
+            .unwrap_or_else(Utc::now)
+    };
+    let mut pages = vec![("index.html".to_string(), newest(&recent))];
+    write_output(
+        out,
+        "index.html",
+        &generate_html(
+            &templates,
+            &recent,
+            &site,
+            &store.review_url,
+            "index.html",
+            &index_nav,
+        )?,
+    )?;
+    for (i, month) in months.iter().enumerate() {
+        let path = format!("archive/{}.html", month);
+        write_output(
+            out,
+            &path,
+            &generate_html(
+                &templates,
+                &acks_by_month[month],
+                &site,
+                &store.review_url,
+                &path,
+                &ArchiveNav::for_month(&months, i),
+            )?,
+        )?;
+        pages.push((path, newest(&acks_by_month[month])));
+    }
+
+    write_output(out, "index.json", &serde_json::to_string_pretty(&acks)?)?;
+    write_output(out, "feed.xml", &generate_feed(&acks, &site))?;
+
+    // One page per PR with every vote it received
+    let mut acks_by_pr: BTreeMap<(&str, u32), Vec<Ack>> = BTreeMap::new();
+    for ack in &acks {
+        acks_by_pr
+            .entry((&ack.repo, ack.pr_number))
+            .or_default()
+            .push(ack.clone());
+    }
+    for ((repo, pr_number), pr_acks) in &acks_by_pr {
+        let path = pr_page_path(repo, *pr_number);
+        write_output(
+            out,
+            &path,
+            &generate_html(
+                &templates,
+                pr_acks,
+                &site,
+                &store.review_url,
+                &path,
+                &ArchiveNav::default(),
+            )?,
+        )?;
+        pages.push((path, newest(pr_acks)));
+    }
+    write_output(out, "sitemap.xml", &generate_sitemap(&site, &pages))?;
+
+    let images = Path::new("images");
+    if images.is_dir() {
+        copy_dir(images, &out.join("images"))?;
+    } else {
+        eprintln!("Warning: no images/ directory found, logos will be missing");
+    }
+    let fonts = Path::new("fonts");
+    if fonts.is_dir() {
+        copy_dir(fonts, &out.join("fonts"))?;
+    }
+    for font in FONTS {
+        if !Path::new(font.path).is_file() {
+            eprintln!(
+                "Warning: {} is missing, pages will use a fallback for {} {}",
+                font.path, font.family, font.weight
             );
-            
-            let rows = date_acks
-                .iter()
-                .map(|ack| {
+        }
+    }
+    // Theme logos don't have to be in images/
+    for logo in [&site.logo, &site.logo_dark] {
+        if Path::new(logo).starts_with("images") {
+            continue;
+        }
+        let target = out.join(logo);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if let Err(e) = fs::copy(logo, &target) {
+            eprintln!("Warning: couldn't copy logo {}: {}", logo, e);
+        }
+    }
+
+    println!(
+        "Generated index.html, index.json, feed.xml, sitemap.xml, {} archive pages and {} PR pages in {}",
+        months.len(),
+        acks_by_pr.len(),
+        out.display()
+    );
+
+    Ok(())
+}
+
+// Parse the command line. --config and --data go anywhere, but the fetch and
+// render options of a bare `ackamoto` run are refused before a subcommand,
+// where they'd be silently ignored (`ackamoto --mode nack render`).
+fn parse_cli<I, T>(args: I) -> Result<(Cli, ArgMatches), clap::Error>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let mut command = Cli::command();
+    let matches = command.try_get_matches_from_mut(args)?;
+    if let Some((name, _)) = matches.subcommand() {
+        let top_level = RenderArgs::augment_args(FetchArgs::augment_args(clap::Command::new("")));
+        for arg in top_level.get_arguments() {
+            if matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine) {
+                return Err(command.error(
+                    ErrorKind::ArgumentConflict,
                     format!(
-                        r#"        <div class="ack-entry">
-            <a href="{}" target="_blank" class="pr-number">#{}</a>
-            <div class="pr-title" title="{}">{}</div>
-            <a href="https://github.com/bitcoin/bitcoin/blob/master/CONTRIBUTING.md#peer-review" target="_blank" class="ack-type">{}</a>
-            <a href="{}" target="_blank" class="commenter">{}</a>
-        </div>
-"#,
-                        ack.pr_url,
-                        ack.pr_number,
-                        ack.pr_title.replace('"', "&quot;"),
-                        ack.pr_title.replace('<', "&lt;").replace('>', "&gt;"),
-                        ack.ack_type,
-                        ack.comment_url,
-                        ack.commenter
-                    )
-                })
-                .collect::<String>();
-            
-            date_header + &rows
-        })
-        .collect::<String>()
-        + r#"    </div>
-</body>
-</html>"#
+                        "--{} must come after the '{}' subcommand",
+                        arg.get_long().unwrap_or_default(),
+                        name
+                    ),
+                ));
+            }
+        }
+    }
+    let cli = Cli::from_arg_matches(&matches)?;
+    Ok((cli, matches))
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
-    let token = env::var("GITHUB_TOKEN").ok();
+    let (mut cli, matches) = parse_cli(env::args_os()).unwrap_or_else(|e| e.exit());
 
-    let client = reqwest::Client::new();
-    let headers = create_headers(token);
+    Config::load(cli.config.as_deref())?.apply(&mut cli, &matches);
+    let (fetch_args, render_args) = match &cli.command {
+        Some(Command::Fetch(args)) => (Some(args), None),
+        Some(Command::Render(args)) => (None, Some(args)),
+        Some(Command::Serve(args)) => (args.refresh.map(|_| &args.fetch), Some(&args.render)),
+        Some(Command::Daemon(args)) => (Some(&args.fetch), Some(&args.render)),
+        Some(Command::Webhook(args)) => (Some(&args.fetch), Some(&args.render)),
+        Some(Command::Query(_) | Command::Stats) => (None, None),
+        None => (Some(&cli.fetch), Some(&cli.render)),
+    };
+    if let Some(args) = fetch_args {
+        config::validate_fetch(args)?;
+    }
+    if let Some(args) = render_args {
+        config::validate_render(args)?;
+    }
+    match &cli.command {
+        Some(Command::Serve(ServeArgs {
+            refresh: Some(minutes),
+            ..
+        })) => {
+            config::validate_minutes("refresh", *minutes)?;
+        }
+        Some(Command::Daemon(args)) => {
+            config::validate_minutes("interval", args.interval)?;
+        }
+        _ => {}
+    }
 
-    println!("Fetching pull requests...");
-    let prs = match fetch_pull_requests(&client, &headers).await {
-        Ok(prs) => {
-            println!("Found {} pull requests", prs.len());
-            prs
-        }
-        Err(e) => {
-            println!("Failed to fetch PRs: {}", e);
-            // Return empty HTML with error message
-            let html = generate_error_html(
-                "Unable to fetch data from GitHub API. This may be due to rate limiting.",
-                &args.mode,
-            );
-            fs::write("index.html", html)?;
-            return Ok(());
+    match &cli.command {
+        Some(Command::Fetch(args)) => fetch(args, &cli.data).await.map(|_| ()),
+        Some(Command::Render(args)) => render(args, &cli.data),
+        Some(Command::Query(args)) => query::query(args, &Store::load(&cli.data)?),
+        Some(Command::Stats) => stats::stats(&Store::load(&cli.data)?),
+        Some(Command::Serve(args)) => serve::serve(args, &cli.data).await,
+        Some(Command::Daemon(args)) => daemon::run(args, &cli.data).await,
+        Some(Command::Webhook(args)) => webhook::run(args, &cli.data).await,
+        None => {
+            if let Err(e) = fetch(&cli.fetch, &cli.data).await {
+                println!("Failed to fetch PRs: {}", e);
+                // Return empty HTML with error message
+                let html = generate_error_html(
+                    &cli.render.templates(),
+                    "Unable to fetch data from GitHub API. This may be due to rate limiting.",
+                    &cli.render.site(),
+                )?;
+                write_output(&cli.render.output_dir, "index.html", &html)?;
+                return Ok(());
+            }
+            render(&cli.render, &cli.data)
         }
-    };
+    }
+}
 
-    let mut all_acks = Vec::new();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    // Check if we have a GitHub token to determine rate limits
-    let has_token = env::var("GITHUB_TOKEN").is_ok();
-    
-    let prs_limit = if has_token {
-        250  // With token, check 250 PRs for both ACKs and NACKs
-    } else {
-        50   // Without token, check 50 PRs for both ACKs and NACKs
-    };
-    
-    if !has_token {
-        println!("Warning: No GITHUB_TOKEN found. API requests will be limited.");
+    fn hostile_ack() -> Ack {
+        Ack {
+            repo: "bitcoin/bitcoin".to_string(),
+            mode: Mode::Ack,
+            pr_number: 1,
+            pr_title: r#"<script>alert(1)</script>" onmouseover="alert(2)" ' & "#.to_string(),
+            pr_url: "javascript:alert(3)".to_string(),
+            pr_state: Some(PrState::Open),
+            pr_merged_at: None,
+            pr_labels: vec![Label {
+                name: "<b>label</b>".to_string(),
+                color: "\" onclick=\"x".to_string(),
+            }],
+            pr_author: Some("<i>author</i>".to_string()),
+            pr_milestone: Some("<u>milestone</u>".to_string()),
+            commenter: "<img src=x onerror=alert(4)>".to_string(),
+            commenter_url: "data:text/html,<b>x</b>".to_string(),
+            comment_url: "java\tscript:alert(5)".to_string(),
+            date: Utc::now(),
+            comment_snippet: "ACK <iframe src=//evil>".to_string(),
+            ack_type: "ACK</a><a href=javascript:alert(6)>".to_string(),
+        }
     }
-    
-    let prs_to_process = prs.iter().take(prs_limit).collect::<Vec<_>>();
 
-    for (i, pr) in prs_to_process.iter().enumerate() {
-        if i % 10 == 0 {
-            println!("Processing PR {}/{}", i + 1, prs_to_process.len());
+    #[test]
+    fn html_escapes_hostile_fields() {
+        let templates = Templates::new(None, DateDisplay::default(), false);
+        let html = generate_html(
+            &templates,
+            &[hostile_ack()],
+            &Site::new(&Mode::Ack, &Theme::default()),
+            "javascript:alert(7)",
+            "index.html",
+            &ArchiveNav::default(),
+        )
+        .unwrap();
+
+        for raw in [
+            "<script>alert",
+            "<img src=x",
+            "</a><a",
+            r#"onmouseover="alert(2)""#,
+            "href=\"javascript:",
+        ] {
+            assert!(!html.contains(raw), "unescaped {:?} in page", raw);
         }
+        assert!(html.contains("&lt;script&gt;alert(1)&lt;/script&gt;&quot; onmouseover"));
+        assert!(html.contains("&#39; &amp;"));
+        assert!(html.contains("&lt;img src=x onerror=alert(4)&gt;"));
+        for raw in ["<b>label", "<i>author", "<u>milestone", r#"" onclick"#] {
+            assert!(!html.contains(raw), "unescaped {:?} in page", raw);
+        }
+        assert!(html.contains(r##"href="#" target="_blank" class="pr-number""##));
+    }
 
-        let comments = fetch_comments_for_pr(&client, &headers, pr.number).await?;
+    #[test]
+    fn error_page_escapes_message() {
+        let templates = Templates::new(None, DateDisplay::default(), false);
+        let html = generate_error_html(
+            &templates,
+            "<b onclick='x()'>rate limited</b>",
+            &Site::new(&Mode::Nack, &Theme::default()),
+        )
+        .unwrap();
 
-        for comment in comments {
-            // Skip bot comments
-            let username_lower = comment.user.login.to_lowercase();
-            if username_lower.contains("bot") || username_lower == "bitcoin-core-ci" {
-                continue;
+        assert!(!html.contains("<b onclick"));
+        assert!(html.contains("&lt;b onclick=&#39;x()&#39;&gt;rate limited&lt;/b&gt;"));
+    }
+
+    #[test]
+    fn feed_escapes_hostile_fields() {
+        let feed = generate_feed(&[hostile_ack()], &Site::new(&Mode::Ack, &Theme::default()));
+
+        for raw in [
+            "<script>",
+            "<img",
+            "<iframe",
+            "href=\"javascript:",
+            "href=\"data:",
+        ] {
+            assert!(!feed.contains(raw), "unescaped {:?} in feed", raw);
+        }
+        assert!(feed.contains(r##"<link href="#"/>"##));
+    }
+
+    #[test]
+    fn groups_by_day_in_display_timezone() {
+        // 21:30 and 02:30 UTC are the same evening in New York
+        let ack_at = |timestamp: &str| Ack {
+            date: timestamp.parse().unwrap(),
+            ..hostile_ack()
+        };
+        let acks = [
+            ack_at("2024-03-06T02:30:00Z"),
+            ack_at("2024-03-05T21:30:00Z"),
+        ];
+        let templates = Templates::new(
+            None,
+            DateDisplay {
+                timezone: chrono_tz::America::New_York,
+                format: "%d/%m/%Y".to_string(),
+            },
+            false,
+        );
+        let html = generate_html(
+            &templates,
+            &acks,
+            &Site::new(&Mode::Ack, &Theme::default()),
+            "",
+            "index.html",
+            &ArchiveNav::default(),
+        )
+        .unwrap();
+
+        assert_eq!(html.matches(r#"class="date-group""#).count(), 1);
+        assert!(html.contains("05/03/2024"));
+        assert!(!html.contains("06/03/2024"));
+    }
+
+    // Basic checks in the spirit of WCAG: a name for every link, image and
+    // input, one main landmark and h1, no skipped heading levels, unique ids
+    // and machine-readable dates
+    fn accessibility_problems(html: &str) -> Vec<String> {
+        use scraper::{ElementRef, Html, Selector};
+
+        let document = Html::parse_document(html);
+        let select = |selector: &str| Selector::parse(selector).unwrap();
+        let attr = |element: &ElementRef, name: &str| {
+            element
+                .value()
+                .attr(name)
+                .map(str::trim)
+                .unwrap_or_default()
+                .to_string()
+        };
+        let mut problems = Vec::new();
+
+        if document.select(&select("html[lang]")).next().is_none() {
+            problems.push("page has no language".to_string());
+        }
+        if document.select(&select("main")).count() != 1 {
+            problems.push("page needs exactly one <main>".to_string());
+        }
+
+        for image in document.select(&select("img")) {
+            if attr(&image, "alt").is_empty() {
+                problems.push(format!("image without alt text: {}", image.html()));
+            }
+        }
+
+        for link in document.select(&select("a")) {
+            let text = link.text().collect::<String>().trim().to_string();
+            let label = attr(&link, "aria-label");
+            if text.is_empty() && label.is_empty() {
+                problems.push(format!("link without a name: {}", link.html()));
+            }
+            // What's read out has to include what's shown, so voice control
+            // users can say the visible text
+            if !label.is_empty() && !label.contains(&text) {
+                problems.push(format!("label {:?} doesn't contain {:?}", label, text));
             }
+            if attr(&link, "target") == "_blank" && !label.contains("new tab") {
+                problems.push(format!("link doesn't say it opens a new tab: {}", text));
+            }
+        }
 
-            if let Some(ack_type) = extract_ack_type(&comment.body, &args.mode) {
-                let ack = Ack {
-                    pr_number: pr.number,
-                    pr_title: pr.title.clone(),
-                    pr_url: pr.html_url.clone(),
-                    commenter: comment.user.login.clone(),
-                    commenter_url: comment.user.html_url.clone(),
-                    comment_url: comment.html_url.clone(),
-                    date: comment.created_at,
-                    comment_snippet: truncate_comment(&comment.body, 200),
-                    ack_type,
-                };
-                all_acks.push(ack);
+        for input in document.select(&select("input")) {
+            let id = attr(&input, "id");
+            let in_label = input
+                .ancestors()
+                .filter_map(ElementRef::wrap)
+                .any(|ancestor| ancestor.value().name() == "label");
+            let labelled_for = !id.is_empty()
+                && document
+                    .select(&select("label[for]"))
+                    .any(|label| attr(&label, "for") == id);
+            if attr(&input, "aria-label").is_empty() && !in_label && !labelled_for {
+                problems.push(format!("input without a label: {}", input.html()));
             }
         }
 
-        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+        let levels: Vec<usize> = document
+            .select(&select("h1, h2, h3, h4, h5, h6"))
+            .map(|heading| heading.value().name()[1..].parse().unwrap())
+            .collect();
+        if levels.iter().filter(|&&level| level == 1).count() != 1 {
+            problems.push("page needs exactly one <h1>".to_string());
+        }
+        let mut previous = 0;
+        for level in levels {
+            if level > previous + 1 {
+                problems.push(format!("heading skips from h{} to h{}", previous, level));
+            }
+            previous = level;
+        }
+
+        let mut ids = HashSet::new();
+        for element in document.select(&select("[id]")) {
+            if !ids.insert(attr(&element, "id")) {
+                problems.push(format!("duplicate id {:?}", attr(&element, "id")));
+            }
+        }
+        for element in document.select(&select("[aria-labelledby]")) {
+            if !ids.contains(&attr(&element, "aria-labelledby")) {
+                problems.push(format!(
+                    "aria-labelledby points nowhere: {}",
+                    element.html()
+                ));
+            }
+        }
+
+        for time in document.select(&select("time")) {
+            let datetime = attr(&time, "datetime");
+            if datetime.parse::<NaiveDate>().is_err() && datetime.parse::<DateTime<Utc>>().is_err()
+            {
+                problems.push(format!("<time> without a valid datetime: {}", time.html()));
+            }
+        }
+
+        problems
+    }
+
+    // WCAG contrast ratio of two "#rgb" or "#rrggbb" colors
+    fn contrast(a: &str, b: &str) -> f64 {
+        let luminance = |color: &str| {
+            let hex = color.trim_start_matches('#');
+            let hex: String = if hex.len() == 3 {
+                hex.chars().flat_map(|c| [c, c]).collect()
+            } else {
+                hex.to_string()
+            };
+            let channel = |i: usize| {
+                let c = u8::from_str_radix(&hex[i..i + 2], 16).unwrap() as f64 / 255.0;
+                if c <= 0.03928 {
+                    c / 12.92
+                } else {
+                    ((c + 0.055) / 1.055).powf(2.4)
+                }
+            };
+            0.2126 * channel(0) + 0.7152 * channel(2) + 0.0722 * channel(4)
+        };
+        let (a, b) = (luminance(a), luminance(b));
+        (a.max(b) + 0.05) / (a.min(b) + 0.05)
+    }
+
+    #[test]
+    fn pages_pass_basic_accessibility_checks() {
+        let merged = Ack {
+            pr_number: 2,
+            pr_title: "wallet: fix fee bump".to_string(),
+            pr_state: Some(PrState::Merged),
+            pr_merged_at: Some(Utc::now()),
+            date: Utc::now() - chrono::Duration::days(1),
+            ..hostile_ack()
+        };
+        let acks = [hostile_ack(), merged];
+        let months = vec!["2024-03".to_string(), "2024-02".to_string()];
+        let templates = Templates::new(None, DateDisplay::default(), false);
+
+        let pages = [
+            (
+                "index",
+                generate_html(
+                    &templates,
+                    &acks,
+                    &Site::new(&Mode::Ack, &Theme::default()),
+                    "",
+                    "index.html",
+                    &ArchiveNav::default(),
+                )
+                .unwrap(),
+            ),
+            (
+                "archive",
+                generate_html(
+                    &templates,
+                    &acks,
+                    &Site::new(&Mode::Nack, &Theme::default()),
+                    "",
+                    "archive/2024-03.html",
+                    &ArchiveNav::for_month(&months, 0),
+                )
+                .unwrap(),
+            ),
+            (
+                "empty",
+                generate_html(
+                    &templates,
+                    &[],
+                    &Site::new(&Mode::Ack, &Theme::default()),
+                    "",
+                    "index.html",
+                    &ArchiveNav::default(),
+                )
+                .unwrap(),
+            ),
+            (
+                "error",
+                generate_error_html(
+                    &templates,
+                    "rate limited",
+                    &Site::new(&Mode::Ack, &Theme::default()),
+                )
+                .unwrap(),
+            ),
+        ];
+        for (name, html) in pages {
+            let problems = accessibility_problems(&html);
+            assert!(problems.is_empty(), "{} page: {:#?}", name, problems);
+        }
     }
 
-    all_acks.sort_by(|a, b| b.date.cmp(&a.date));
+    #[test]
+    fn global_options_go_before_or_after_the_subcommand() {
+        for args in [
+            &["ackamoto", "--data", "x.json", "query"][..],
+            &["ackamoto", "query", "--data", "x.json"],
+        ] {
+            let cli = Cli::try_parse_from(args).unwrap();
+            assert_eq!(cli.data, Path::new("x.json"));
+            assert!(matches!(cli.command, Some(Command::Query(_))));
+            assert!(parse_cli(args).is_ok());
+        }
+        for args in [
+            &["ackamoto", "--config", "prod.toml", "daemon"][..],
+            &["ackamoto", "daemon", "--config", "prod.toml"],
+        ] {
+            let (cli, _) = parse_cli(args).unwrap();
+            assert_eq!(cli.config.as_deref(), Some(Path::new("prod.toml")));
+            assert!(matches!(cli.command, Some(Command::Daemon(_))));
+        }
 
-    println!("Found {} ACKs total", all_acks.len());
+        // Fetch and render options belong to the subcommand
+        let (cli, _) = parse_cli(["ackamoto", "render", "--mode", "nack"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Render(args)) if args.mode == Mode::Nack));
+        let misplaced = parse_cli(["ackamoto", "--mode", "nack", "render"]);
+        assert_eq!(misplaced.err().unwrap().kind(), ErrorKind::ArgumentConflict);
+        assert!(parse_cli(["ackamoto", "--mode", "nack"]).is_ok());
+    }
 
-    let html = generate_html(&all_acks, &args.mode);
-    fs::write("index.html", html)?;
-    println!("Generated index.html");
+    #[test]
+    fn snippets_cut_long_lines_and_skip_quotes() {
+        let long = "ACK ".to_string() + &"é".repeat(300);
+        let snippet = truncate_comment(&long, 200);
+        assert_eq!(snippet.chars().count(), 203);
+        assert!(snippet.starts_with("ACK éé") && snippet.ends_with("é..."));
 
-    Ok(())
+        let quoted = "> a long quote that would take up the whole snippet\nACK abc123\nTested";
+        assert_eq!(truncate_comment(quoted, 10), "ACK abc123...");
+        assert_eq!(truncate_comment(quoted, 200), "ACK abc123\nTested");
+    }
+
+    #[test]
+    fn pr_pages_are_kept_apart_by_repo() {
+        assert_eq!(pr_page_path("bitcoin/core", 5), "pr/bitcoin/core/5.html");
+        assert_eq!(pr_page_path("bitcoin/gui", 5), "pr/bitcoin/gui/5.html");
+        assert_eq!(pr_page_path("mail:dev.mbox", 5), "pr/mail/dev.mbox/5.html");
+        assert_eq!(pr_page_path("../../etc/x y", 5), "pr/etc/x-y/5.html");
+
+        let gui = Ack {
+            repo: "bitcoin/gui".to_string(),
+            ..hostile_ack()
+        };
+        let html = generate_html(
+            &Templates::new(None, DateDisplay::default(), false),
+            &[hostile_ack(), gui],
+            &Site::new(&Mode::Ack, &Theme::default()),
+            "",
+            "archive/2024-03.html",
+            &ArchiveNav::default(),
+        )
+        .unwrap();
+        assert!(html.contains(r#"href="../pr/bitcoin/bitcoin/1.html""#));
+        assert!(html.contains(r#"href="../pr/bitcoin/gui/1.html""#));
+    }
+
+    #[test]
+    fn theme_replaces_branding() {
+        let theme: Theme = toml::from_str(
+            r##"
+            project = "Example Server"
+            footer = "Run by [Example](https://example.com) <script>alert(1)</script>"
+            colors = { background = "#fdfdfd", text = "#111" }
+
+            [ack]
+            name = "Example ACKs"
+            domain = "acks.example.com"
+            logo = "branding/logo.svg"
+            links = [{ text = "Dashboard", url = "https://dash.example.com" }]
+            "##,
+        )
+        .unwrap();
+        theme.validate().unwrap();
+        let site = Site::new(&Mode::Ack, &theme);
+        let templates = Templates::new(None, DateDisplay::default(), false);
+        let html = generate_html(
+            &templates,
+            &[],
+            &site,
+            "",
+            "index.html",
+            &ArchiveNav::default(),
+        )
+        .unwrap();
+
+        assert!(html.contains("<title>Example Server ACKs - acks.example.com</title>"));
+        assert!(html.contains(r#"<link rel="canonical" href="https://acks.example.com/">"#));
+        // Without a dark variant the one logo is shown in both schemes
+        assert!(html.contains(r#"src="branding/logo.svg" alt="Example ACKs" class="logo">"#));
+        assert!(!html.contains(r#"class="logo logo-dark""#));
+        assert!(html.contains(
+            r#"<a href="https://dash.example.com" style="color: inherit;">Dashboard</a>"#
+        ));
+        assert!(!html.contains("nackamoto"));
+        assert!(html.contains("--bg-color: #fdfdfd;"));
+        assert!(html.contains(
+            r#"<a href="https://example.com" rel="nofollow noopener noreferrer">Example</a>"#
+        ));
+        assert!(!html.contains("<script>alert(1)"));
+        assert!(accessibility_problems(&html).is_empty());
+    }
+
+    #[test]
+    fn theme_rejects_css_injection_and_outside_logos() {
+        for toml in [
+            r##"colors = { text = "red; } body { display: none" }"##,
+            r#"ack = { logo = "../secret.png" }"#,
+            r#"nack = { logo_dark = "/etc/passwd" }"#,
+            r#"ack = { domain = "x.com/\"><script>alert(1)</script>" }"#,
+        ] {
+            let theme: Theme = toml::from_str(toml).unwrap();
+            assert!(theme.validate().is_err(), "{}", toml);
+        }
+    }
+
+    #[test]
+    fn feed_escapes_the_site_url() {
+        // As read from a CNAME file, which isn't validated
+        let theme: Theme = toml::from_str(r#"ack = { domain = "a.com/?x=1&y=\"<z>" }"#).unwrap();
+        let feed = generate_feed(&[hostile_ack()], &Site::new(&Mode::Ack, &theme));
+        assert!(feed.contains(r#"<link href="https://a.com/?x=1&amp;y=&quot;&lt;z&gt;/"/>"#));
+        assert!(!feed.contains("<z>"));
+        assert!(!feed.contains("<script>"));
+    }
+
+    #[test]
+    fn text_colors_have_enough_contrast() {
+        // The light scheme's colors come first in style.css, then the dark one's
+        let css = include_str!("../templates/style.css");
+        let colors = |name: &str| -> Vec<&str> {
+            css.lines()
+                .filter_map(|line| line.trim().strip_prefix(name))
+                .map(|value| value.trim_start_matches(':').trim().trim_end_matches(';'))
+                .collect()
+        };
+        let backgrounds = colors("--bg-color");
+        assert_eq!(backgrounds.len(), 2);
+        for name in ["--text-color", "--muted-color"] {
+            for (color, background) in colors(name).into_iter().zip(&backgrounds) {
+                let ratio = contrast(color, background);
+                assert!(
+                    ratio >= 4.5,
+                    "{} {} on {}: {:.2}",
+                    name,
+                    color,
+                    background,
+                    ratio
+                );
+            }
+        }
+    }
 }
@@ -0,0 +1,75 @@
+use ammonia::Builder;
+use pulldown_cmark::{html, Options, Parser};
+use std::collections::HashSet;
+
+// What a comment snippet may keep once rendered: paragraphs, emphasis, code,
+// lists, quotes and links. Everything else (images, headings, tables, raw
+// HTML, event handlers, styles) is stripped.
+const ALLOWED_TAGS: &[&str] = &[
+    "p",
+    "br",
+    "em",
+    "strong",
+    "del",
+    "code",
+    "pre",
+    "a",
+    "ul",
+    "ol",
+    "li",
+    "blockquote",
+];
+
+// Render a comment snippet from GitHub-flavoured Markdown to sanitized HTML.
+// The snippet is cut off mid-comment, so unterminated code blocks and the
+// like are expected; the parser closes them.
+pub fn render_snippet(markdown: &str) -> String {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, Parser::new_ext(markdown, options));
+
+    Builder::empty()
+        .tags(ALLOWED_TAGS.iter().copied().collect())
+        .tag_attributes(
+            [("a", ["href"].into_iter().collect())]
+                .into_iter()
+                .collect(),
+        )
+        .url_schemes(HashSet::from(["http", "https", "mailto"]))
+        .link_rel(Some("nofollow noopener noreferrer"))
+        .clean(&unsafe_html)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_code_links_and_emphasis() {
+        let html = render_snippet("ACK `abc123`, *nice* [work](https://example.com)");
+        assert_eq!(
+            html,
+            "<p>ACK <code>abc123</code>, <em>nice</em> <a href=\"https://example.com\" rel=\"nofollow noopener noreferrer\">work</a></p>\n"
+        );
+    }
+
+    #[test]
+    fn strips_raw_html_and_script_links() {
+        let html = render_snippet(
+            "NACK <script>alert(1)</script> <img src=x onerror=alert(2)> [x](javascript:alert(3))",
+        );
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("<img"));
+        assert!(!html.contains("onerror"));
+        assert!(!html.contains("javascript:"));
+    }
+
+    #[test]
+    fn closes_truncated_code_blocks() {
+        let html = render_snippet("utACK\n```\nfn main() {...");
+        assert!(html.ends_with("</code></pre>\n"));
+    }
+}
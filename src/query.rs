@@ -0,0 +1,206 @@
+use std::io::{self, Write};
+
+use crate::store::Store;
+use crate::{format_date, Ack, QueryArgs, QueryFormat};
+
+fn matches(ack: &Ack, args: &QueryArgs) -> bool {
+    let date = ack.date.date_naive();
+
+    args.mode.as_ref().is_none_or(|mode| &ack.mode == mode)
+        && args
+            .ack_type
+            .as_ref()
+            .is_none_or(|ack_type| ack.ack_type.eq_ignore_ascii_case(ack_type))
+        && args
+            .reviewer
+            .as_ref()
+            .is_none_or(|reviewer| ack.commenter.eq_ignore_ascii_case(reviewer))
+        && args.pr.is_none_or(|pr| ack.pr_number == pr)
+        && args
+            .repo
+            .as_ref()
+            .is_none_or(|repo| ack.repo.eq_ignore_ascii_case(repo))
+        && args.since.is_none_or(|since| date >= since)
+        && args.until.is_none_or(|until| date <= until)
+}
+
+// Quote a CSV field when it contains a separator, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn query(args: &QueryArgs, store: &Store) -> Result<(), Box<dyn std::error::Error>> {
+    let acks = store
+        .acks
+        .iter()
+        .filter(|ack| matches(ack, args))
+        .take(args.limit.unwrap_or(usize::MAX));
+
+    write_acks(io::stdout().lock(), acks, &args.format)
+}
+
+// A reader that stops early (`ackamoto query | head`) closes the pipe, which
+// just ends the output
+fn write_acks<'a>(
+    mut out: impl Write,
+    acks: impl Iterator<Item = &'a Ack>,
+    format: &QueryFormat,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match write_rows(&mut out, acks, format).and_then(|()| out.flush()) {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => Ok(result?),
+    }
+}
+
+fn write_rows<'a>(
+    out: &mut impl Write,
+    acks: impl Iterator<Item = &'a Ack>,
+    format: &QueryFormat,
+) -> io::Result<()> {
+    match format {
+        QueryFormat::Table => {
+            for ack in acks {
+                writeln!(
+                    out,
+                    "{}  {:<16} {:<20} {}#{:<6} {}",
+                    format_date(&ack.date),
+                    ack.ack_type,
+                    ack.commenter,
+                    ack.repo,
+                    ack.pr_number,
+                    ack.pr_title
+                )?;
+            }
+        }
+        QueryFormat::Jsonl => {
+            for ack in acks {
+                writeln!(out, "{}", serde_json::to_string(ack)?)?;
+            }
+        }
+        QueryFormat::Csv => {
+            writeln!(
+                out,
+                "date,repo,pr_number,pr_title,ack_type,commenter,comment_url"
+            )?;
+            for ack in acks {
+                writeln!(
+                    out,
+                    "{},{},{},{},{},{},{}",
+                    ack.date.to_rfc3339(),
+                    csv_field(&ack.repo),
+                    ack.pr_number,
+                    csv_field(&ack.pr_title),
+                    csv_field(&ack.ack_type),
+                    csv_field(&ack.commenter),
+                    csv_field(&ack.comment_url)
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Cli, Command, Mode};
+    use chrono::{TimeZone, Utc};
+    use clap::Parser;
+
+    fn query_args(flags: &[&str]) -> QueryArgs {
+        let args = ["ackamoto", "query"].iter().chain(flags);
+        match Cli::try_parse_from(args).unwrap().command {
+            Some(Command::Query(args)) => args,
+            _ => unreachable!(),
+        }
+    }
+
+    fn ack() -> Ack {
+        Ack {
+            repo: "bitcoin/bitcoin".to_string(),
+            mode: Mode::Ack,
+            pr_number: 28000,
+            pr_title: "wallet: fix fee bump".to_string(),
+            pr_url: "https://github.com/bitcoin/bitcoin/pull/28000".to_string(),
+            pr_state: None,
+            pr_merged_at: None,
+            pr_labels: Vec::new(),
+            pr_author: None,
+            pr_milestone: None,
+            commenter: "Alice".to_string(),
+            commenter_url: "https://github.com/Alice".to_string(),
+            comment_url: "https://github.com/bitcoin/bitcoin/pull/28000#issuecomment-1".to_string(),
+            date: Utc.with_ymd_and_hms(2024, 3, 5, 23, 59, 0).unwrap(),
+            comment_snippet: "Concept ACK".to_string(),
+            ack_type: "Concept ACK".to_string(),
+        }
+    }
+
+    #[test]
+    fn filters_votes() {
+        let ack = ack();
+        let matching: &[&[&str]] = &[
+            &[],
+            &["--reviewer", "alice"],
+            &["--type", "concept ack"],
+            &["--repo", "Bitcoin/Bitcoin"],
+            &["--pr", "28000", "--mode", "ack"],
+            // Both ends of the range are included
+            &["--since", "2024-03-05", "--until", "2024-03-05"],
+        ];
+        for flags in matching {
+            assert!(matches(&ack, &query_args(flags)), "{:?}", flags);
+        }
+
+        let other: &[&[&str]] = &[
+            &["--reviewer", "bob"],
+            &["--type", "ACK"],
+            &["--repo", "bitcoin/gui"],
+            &["--pr", "28001"],
+            &["--mode", "nack"],
+            &["--since", "2024-03-06"],
+            &["--until", "2024-03-04"],
+        ];
+        for flags in other {
+            assert!(!matches(&ack, &query_args(flags)), "{:?}", flags);
+        }
+    }
+
+    #[test]
+    fn quotes_csv_fields() {
+        assert_eq!(csv_field("bitcoin/bitcoin"), "bitcoin/bitcoin");
+        assert_eq!(csv_field("wallet, gui"), "\"wallet, gui\"");
+        assert_eq!(csv_field(r#"say "ACK""#), r#""say ""ACK""""#);
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    // Stands in for stdout after `head` has exited
+    struct ClosedPipe;
+
+    impl Write for ClosedPipe {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+    }
+
+    #[test]
+    fn closed_pipe_ends_output() {
+        let acks = [ack(), ack()];
+        let mut csv = Vec::new();
+        write_acks(&mut csv, acks.iter(), &QueryFormat::Csv).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap().lines().count(), 3);
+
+        for format in [QueryFormat::Table, QueryFormat::Jsonl, QueryFormat::Csv] {
+            assert!(write_acks(ClosedPipe, acks.iter(), &format).is_ok());
+        }
+    }
+}
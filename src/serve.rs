@@ -0,0 +1,371 @@
+use std::collections::HashMap;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::config::validate_minutes;
+use crate::{fetch, render, FetchArgs, RenderArgs};
+
+// How often the data file, images/, fonts/ and templates are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_REQUEST_HEADER: usize = 8192;
+const RELOAD_PATH: &str = "/__livereload";
+
+// Added to every HTML page served: polls the site version and reloads the
+// page once it changes, i.e. after a re-render
+const RELOAD_SCRIPT: &str = r#"<script>
+(function () {
+    var version = null;
+    setInterval(function () {
+        fetch("/__livereload").then(function (r) { return r.text(); }).then(function (v) {
+            if (version !== null && v !== version) location.reload();
+            version = v;
+        }).catch(function () {});
+    }, 1000);
+})();
+</script>
+"#;
+
+#[derive(clap::Args)]
+pub struct ServeArgs {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:8000")]
+    listen: SocketAddr,
+    /// Also fetch new data every MINUTES, for running as a dashboard
+    #[arg(long, value_name = "MINUTES")]
+    pub refresh: Option<u64>,
+    #[command(flatten)]
+    pub fetch: FetchArgs,
+    #[command(flatten)]
+    pub render: RenderArgs,
+}
+
+// Newest modification time and number of files under `paths`, so edits,
+// additions and deletions all count as a change
+fn snapshot(paths: &[&Path]) -> (Option<SystemTime>, usize) {
+    fn walk(path: &Path, latest: &mut Option<SystemTime>, count: &mut usize) {
+        let Ok(metadata) = fs::metadata(path) else {
+            return;
+        };
+        if metadata.is_dir() {
+            if let Ok(entries) = fs::read_dir(path) {
+                for entry in entries.flatten() {
+                    walk(&entry.path(), latest, count);
+                }
+            }
+        } else {
+            *count += 1;
+            if let Ok(modified) = metadata.modified() {
+                *latest = (*latest).max(Some(modified));
+            }
+        }
+    }
+
+    let mut latest = None;
+    let mut count = 0;
+    for path in paths {
+        walk(path, &mut latest, &mut count);
+    }
+    (latest, count)
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("json") => "application/json",
+        Some("xml") => "application/atom+xml",
+        Some("css") => "text/css",
+        Some("js") => "text/javascript",
+        Some("png") => "image/png",
+        Some("svg") => "image/svg+xml",
+        Some("woff2") => "font/woff2",
+        Some("txt") => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or_default();
+            if let Ok(byte) = u8::from_str_radix(hex, 16) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// Map a request path onto a file in the output directory. Anything that would
+// climb out of it is refused.
+fn resolve(root: &Path, request_path: &str) -> Option<PathBuf> {
+    let decoded = percent_decode(request_path);
+    let relative = Path::new(decoded.trim_start_matches('/'));
+    if relative
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_)))
+    {
+        return None;
+    }
+
+    let path = root.join(relative);
+    if path.is_dir() {
+        Some(path.join("index.html"))
+    } else {
+        Some(path)
+    }
+}
+
+pub struct Request {
+    pub method: String,
+    // The path as sent, with any query string
+    pub target: String,
+    // Keyed by lowercase name
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+// Read one request with a body of up to `max_body` bytes. Requests that are
+// too large or malformed are answered here, and like a client hanging up
+// early give `None`: there's nothing left to respond to.
+pub async fn read_request(
+    stream: &mut TcpStream,
+    max_body: usize,
+) -> std::io::Result<Option<Request>> {
+    let mut buf = Vec::new();
+    let mut chunk = [0; 4096];
+    let header_end = loop {
+        let end = buf.windows(4).position(|window| window == b"\r\n\r\n");
+        if end.unwrap_or(buf.len()) > MAX_REQUEST_HEADER {
+            let status = "431 Request Header Fields Too Large";
+            respond(stream, status, "text/plain", b"", false).await?;
+            return Ok(None);
+        }
+        if let Some(end) = end {
+            break end;
+        }
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).into_owned();
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let target = request_line.next().unwrap_or("/").to_string();
+    let headers: HashMap<String, String> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+        .collect();
+
+    let length = match headers.get("content-length").map(|length| length.parse()) {
+        None => 0,
+        Some(Ok(length)) => length,
+        Some(Err(_)) => {
+            let body = b"Invalid Content-Length";
+            respond(stream, "400 Bad Request", "text/plain", body, false).await?;
+            return Ok(None);
+        }
+    };
+    if length > max_body {
+        respond(stream, "413 Payload Too Large", "text/plain", b"", false).await?;
+        return Ok(None);
+    }
+
+    let mut body = buf[header_end + 4..].to_vec();
+    while body.len() < length {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    body.truncate(length);
+
+    Ok(Some(Request {
+        method,
+        target,
+        headers,
+        body,
+    }))
+}
+
+pub async fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+    head: bool,
+) -> std::io::Result<()> {
+    let header = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    stream.write_all(header.as_bytes()).await?;
+    if !head {
+        stream.write_all(body).await?;
+    }
+    stream.flush().await
+}
+
+async fn handle(mut stream: TcpStream, root: &Path, version: &AtomicU64) -> std::io::Result<()> {
+    // Nothing served here takes a body; a small one is read and ignored
+    let Some(request) = read_request(&mut stream, MAX_REQUEST_HEADER).await? else {
+        return Ok(());
+    };
+    let path = request.target.split(['?', '#']).next().unwrap_or("/");
+
+    let head = match request.method.as_str() {
+        "GET" => false,
+        "HEAD" => true,
+        _ => {
+            return respond(
+                &mut stream,
+                "405 Method Not Allowed",
+                "text/plain",
+                b"",
+                false,
+            )
+            .await;
+        }
+    };
+
+    if path == RELOAD_PATH {
+        let current = version.load(Ordering::SeqCst).to_string();
+        return respond(
+            &mut stream,
+            "200 OK",
+            "text/plain",
+            current.as_bytes(),
+            head,
+        )
+        .await;
+    }
+
+    let Some(file) = resolve(root, path) else {
+        return respond(
+            &mut stream,
+            "403 Forbidden",
+            "text/plain",
+            b"Forbidden",
+            head,
+        )
+        .await;
+    };
+    let Ok(mut body) = fs::read(&file) else {
+        return respond(
+            &mut stream,
+            "404 Not Found",
+            "text/plain",
+            b"Not found",
+            head,
+        )
+        .await;
+    };
+
+    let content_type = content_type(&file);
+    if content_type.starts_with("text/html") {
+        let html = String::from_utf8_lossy(&body);
+        let html = match html.rfind("</body>") {
+            Some(pos) => format!("{}{}{}", &html[..pos], RELOAD_SCRIPT, &html[pos..]),
+            None => format!("{}{}", html, RELOAD_SCRIPT),
+        };
+        body = html.into_bytes();
+    }
+
+    respond(&mut stream, "200 OK", content_type, &body, head).await
+}
+
+async fn accept_loop(listener: TcpListener, root: PathBuf, version: Arc<AtomicU64>) {
+    let root = Arc::new(root);
+    loop {
+        let (stream, _) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                eprintln!("Failed to accept connection: {}", e);
+                continue;
+            }
+        };
+        let root = root.clone();
+        let version = version.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle(stream, &root, &version).await {
+                eprintln!("Failed to serve request: {}", e);
+            }
+        });
+    }
+}
+
+// Serve the site on localhost, re-rendering (and reloading open pages) when
+// the data file, images, fonts or templates change, and optionally fetching on an
+// interval
+pub async fn serve(args: &ServeArgs, data: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut watched = vec![data, Path::new("images"), Path::new("fonts")];
+    if let Some(templates) = &args.render.templates {
+        watched.push(templates);
+    }
+    let version = Arc::new(AtomicU64::new(0));
+
+    render(&args.render, data)?;
+    let mut last_snapshot = snapshot(&watched);
+
+    let listener = TcpListener::bind(args.listen).await?;
+    println!(
+        "Serving {} at http://{}/ (Ctrl-C to stop)",
+        args.render.output_dir.display(),
+        listener.local_addr()?
+    );
+    tokio::spawn(accept_loop(
+        listener,
+        args.render.output_dir.clone(),
+        version.clone(),
+    ));
+
+    let refresh = args
+        .refresh
+        .map(|minutes| validate_minutes("refresh", minutes))
+        .transpose()?;
+    let mut next_refresh = refresh.map(|interval| Instant::now() + interval);
+
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+
+        if let (Some(interval), Some(at)) = (refresh, next_refresh) {
+            if Instant::now() >= at {
+                if let Err(e) = fetch(&args.fetch, data).await {
+                    eprintln!("Failed to refresh data: {}", e);
+                }
+                next_refresh = Some(Instant::now() + interval);
+            }
+        }
+
+        let current = snapshot(&watched);
+        if current != last_snapshot {
+            last_snapshot = current;
+            match render(&args.render, data) {
+                Ok(()) => {
+                    version.fetch_add(1, Ordering::SeqCst);
+                }
+                Err(e) => eprintln!("Failed to render: {}", e),
+            }
+        }
+    }
+}
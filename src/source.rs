@@ -0,0 +1,288 @@
+use async_trait::async_trait;
+use futures::stream::{self, StreamExt};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{Comment, PullRequest};
+
+// Where PRs and their discussion come from. The live implementations talk to
+// GitHub; `FixtureSource` replays JSON recorded by `RecordingSource` so runs can
+// be reproduced offline.
+#[async_trait(?Send)]
+pub trait Source {
+    // Most recent PRs first, at most `limit` of them
+    async fn list_pull_requests(
+        &self,
+        limit: usize,
+    ) -> Result<Vec<PullRequest>, Box<dyn std::error::Error>>;
+
+    async fn list_comments(
+        &self,
+        pr_number: u32,
+    ) -> Result<Vec<Comment>, Box<dyn std::error::Error>>;
+
+    async fn list_reviews(
+        &self,
+        pr_number: u32,
+    ) -> Result<Vec<Comment>, Box<dyn std::error::Error>>;
+
+    // Requests that still failed after retries, reported at the end of a run
+    fn failures(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+// Fetch up to `limit` PRs, then their comments and reviews with at most
+// `concurrency` PRs in flight. `buffered` yields results in PR order so the
+// output stays deterministic regardless of completion order.
+pub async fn collect_pull_requests(
+    source: &dyn Source,
+    limit: usize,
+    concurrency: usize,
+) -> Result<Vec<(PullRequest, Vec<Comment>)>, Box<dyn std::error::Error>> {
+    let prs = source.list_pull_requests(limit).await?;
+    let total = prs.len();
+
+    let results = stream::iter(prs.into_iter().enumerate())
+        .map(|(i, pr)| async move {
+            if i % 10 == 0 {
+                println!("Processing PR {}/{}", i + 1, total);
+            }
+
+            let (comments, reviews) = futures::join!(
+                source.list_comments(pr.number),
+                source.list_reviews(pr.number)
+            );
+
+            let mut comments = match comments {
+                Ok(comments) => comments,
+                Err(e) => {
+                    eprintln!("Failed to fetch comments for PR {}: {}", pr.number, e);
+                    Vec::new()
+                }
+            };
+            match reviews {
+                Ok(reviews) => comments.extend(reviews),
+                Err(e) => eprintln!("Failed to fetch reviews for PR {}: {}", pr.number, e),
+            }
+
+            (pr, comments)
+        })
+        .buffered(concurrency.max(1))
+        .collect::<Vec<_>>()
+        .await;
+
+    Ok(results)
+}
+
+// Fixture layout shared by `FixtureSource` and `RecordingSource`:
+//   <dir>/pulls.json
+//   <dir>/comments/<pr>.json
+//   <dir>/reviews/<pr>.json
+fn pulls_path(dir: &Path) -> PathBuf {
+    dir.join("pulls.json")
+}
+
+fn comments_path(dir: &Path, pr_number: u32) -> PathBuf {
+    dir.join("comments").join(format!("{}.json", pr_number))
+}
+
+fn reviews_path(dir: &Path, pr_number: u32) -> PathBuf {
+    dir.join("reviews").join(format!("{}.json", pr_number))
+}
+
+fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T, Box<dyn std::error::Error>> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let value =
+        serde_json::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(value)
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(value)?)?;
+    Ok(())
+}
+
+pub struct FixtureSource {
+    dir: PathBuf,
+}
+
+impl FixtureSource {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        FixtureSource { dir: dir.into() }
+    }
+}
+
+#[async_trait(?Send)]
+impl Source for FixtureSource {
+    async fn list_pull_requests(
+        &self,
+        limit: usize,
+    ) -> Result<Vec<PullRequest>, Box<dyn std::error::Error>> {
+        let mut prs: Vec<PullRequest> = read_json(&pulls_path(&self.dir))?;
+        prs.truncate(limit);
+        Ok(prs)
+    }
+
+    async fn list_comments(
+        &self,
+        pr_number: u32,
+    ) -> Result<Vec<Comment>, Box<dyn std::error::Error>> {
+        read_json(&comments_path(&self.dir, pr_number))
+    }
+
+    async fn list_reviews(
+        &self,
+        pr_number: u32,
+    ) -> Result<Vec<Comment>, Box<dyn std::error::Error>> {
+        read_json(&reviews_path(&self.dir, pr_number))
+    }
+}
+
+// Passes everything through to `inner` and saves each successful response in
+// the fixture layout, so `--record dir` followed by `--fixtures dir` replays
+// the same run.
+pub struct RecordingSource {
+    inner: Box<dyn Source>,
+    dir: PathBuf,
+}
+
+impl RecordingSource {
+    pub fn new(inner: Box<dyn Source>, dir: impl Into<PathBuf>) -> Self {
+        RecordingSource {
+            inner,
+            dir: dir.into(),
+        }
+    }
+
+    fn record<T: Serialize>(&self, path: &Path, value: &T) {
+        if let Err(e) = write_json(path, value) {
+            eprintln!("Failed to record {}: {}", path.display(), e);
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Source for RecordingSource {
+    async fn list_pull_requests(
+        &self,
+        limit: usize,
+    ) -> Result<Vec<PullRequest>, Box<dyn std::error::Error>> {
+        let prs = self.inner.list_pull_requests(limit).await?;
+        self.record(&pulls_path(&self.dir), &prs);
+        Ok(prs)
+    }
+
+    async fn list_comments(
+        &self,
+        pr_number: u32,
+    ) -> Result<Vec<Comment>, Box<dyn std::error::Error>> {
+        let comments = self.inner.list_comments(pr_number).await?;
+        self.record(&comments_path(&self.dir, pr_number), &comments);
+        Ok(comments)
+    }
+
+    async fn list_reviews(
+        &self,
+        pr_number: u32,
+    ) -> Result<Vec<Comment>, Box<dyn std::error::Error>> {
+        let reviews = self.inner.list_reviews(pr_number).await?;
+        self.record(&reviews_path(&self.dir, pr_number), &reviews);
+        Ok(reviews)
+    }
+
+    fn failures(&self) -> Vec<String> {
+        self.inner.failures()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{collect_acks, Cli, Command};
+    use clap::Parser;
+
+    fn comment(login: &str, body: &str, date: &str) -> serde_json::Value {
+        serde_json::json!({
+            "body": body,
+            "created_at": date,
+            "html_url": format!("https://github.com/bitcoin/bitcoin/pull/0#{}", login),
+            "user": { "login": login, "html_url": format!("https://github.com/{}", login) },
+        })
+    }
+
+    #[tokio::test]
+    async fn fixtures_are_replayed_in_listing_order_with_reviews_merged() {
+        let dir = std::env::temp_dir().join(format!("ackamoto-fixtures-{}", std::process::id()));
+        let pulls = serde_json::json!([
+            { "number": 30, "title": "Newest", "html_url": "https://github.com/bitcoin/bitcoin/pull/30" },
+            { "number": 20, "title": "No comments", "html_url": "https://github.com/bitcoin/bitcoin/pull/20" },
+            { "number": 10, "title": "Oldest", "html_url": "https://github.com/bitcoin/bitcoin/pull/10" },
+        ]);
+        write_json(&pulls_path(&dir), &pulls).unwrap();
+        write_json(
+            &comments_path(&dir, 30),
+            &[comment("alice", "ACK abc123", "2026-10-03T00:00:00Z")],
+        )
+        .unwrap();
+        write_json(
+            &reviews_path(&dir, 30),
+            &[comment("bob", "NACK, unsafe", "2026-10-04T00:00:00Z")],
+        )
+        .unwrap();
+        // PR 20 has reviews but its comments file is missing
+        write_json(
+            &reviews_path(&dir, 20),
+            &[comment("carol", "utACK def456", "2026-10-02T00:00:00Z")],
+        )
+        .unwrap();
+        write_json(
+            &comments_path(&dir, 10),
+            &[
+                comment("dave", "Concept ACK", "2026-10-01T00:00:00Z"),
+                comment("ci-bot", "ACK from a bot", "2026-10-01T01:00:00Z"),
+            ],
+        )
+        .unwrap();
+        write_json(&reviews_path(&dir, 10), &Vec::<Comment>::new()).unwrap();
+
+        let source = FixtureSource::new(&dir);
+        let prs = collect_pull_requests(&source, usize::MAX, 3).await.unwrap();
+        let numbers: Vec<u32> = prs.iter().map(|(pr, _)| pr.number).collect();
+        assert_eq!(numbers, [30, 20, 10]);
+        // Comments come first, then reviews
+        let logins: Vec<&str> = prs[0].1.iter().map(|c| c.user.login.as_str()).collect();
+        assert_eq!(logins, ["alice", "bob"]);
+        assert_eq!(prs[1].1.len(), 1);
+        assert_eq!(prs[1].1[0].user.login, "carol");
+
+        let limited = collect_pull_requests(&source, 2, 1).await.unwrap();
+        assert_eq!(limited.len(), 2);
+
+        let args = match Cli::try_parse_from(["ackamoto", "fetch"]).unwrap().command {
+            Some(Command::Fetch(args)) => args,
+            _ => unreachable!(),
+        };
+        let acks = collect_acks(&args, prs);
+        let found: Vec<(u32, &str, &str)> = acks
+            .iter()
+            .map(|ack| (ack.pr_number, ack.commenter.as_str(), ack.ack_type.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (30, "alice", "ACK"),
+                (30, "bob", "NACK"),
+                (20, "carol", "utACK"),
+                (10, "dave", "Concept ACK"),
+            ]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+
+use crate::store::Store;
+use crate::{format_date, Mode};
+
+// Print (count, name) pairs, biggest first and then alphabetically
+fn print_top(title: &str, counts: HashMap<String, usize>, limit: usize) {
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    println!("\n{}:", title);
+    for (name, count) in counts.into_iter().take(limit) {
+        println!("  {:>5}  {}", count, name);
+    }
+}
+
+pub fn stats(store: &Store) -> Result<(), Box<dyn std::error::Error>> {
+    if store.acks.is_empty() {
+        println!("No ACKs or NACKs stored yet; run `ackamoto fetch` first.");
+        return Ok(());
+    }
+
+    let acks = store
+        .acks
+        .iter()
+        .filter(|ack| ack.mode == Mode::Ack)
+        .count();
+    let nacks = store.acks.len() - acks;
+    println!("{} ACKs, {} NACKs", acks, nacks);
+
+    let oldest = store.acks.iter().map(|ack| &ack.date).min().unwrap();
+    let newest = store.acks.iter().map(|ack| &ack.date).max().unwrap();
+    println!("From {} to {}", format_date(oldest), format_date(newest));
+    if let Some(updated_at) = &store.updated_at {
+        println!("Last fetched {}", updated_at.format("%Y-%m-%d %H:%M UTC"));
+    }
+
+    let mut by_type = HashMap::new();
+    let mut by_reviewer = HashMap::new();
+    let mut by_pr = HashMap::new();
+    for ack in &store.acks {
+        *by_type.entry(ack.ack_type.clone()).or_default() += 1;
+        *by_reviewer.entry(ack.commenter.clone()).or_default() += 1;
+        *by_pr
+            .entry(format!("#{} {}", ack.pr_number, ack.pr_title))
+            .or_default() += 1;
+    }
+
+    print_top("By type", by_type, usize::MAX);
+    print_top("Top reviewers", by_reviewer, 10);
+    print_top("Most reviewed PRs", by_pr, 10);
+
+    Ok(())
+}
@@ -0,0 +1,103 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::{write_atomic, Ack, Mode, PullRequest};
+
+// Everything `fetch` has collected so far. Each fetch merges into it, so the
+// history grows beyond the window of PRs a single run looks at, and `render`,
+// `query` and `stats` work from it without touching the network.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Store {
+    pub updated_at: Option<DateTime<Utc>>,
+    // Where the ACK type badges link to, from the most recent fetch
+    #[serde(default)]
+    pub review_url: String,
+    pub acks: Vec<Ack>,
+}
+
+impl Store {
+    // A missing file is an empty store, so the first fetch needs no setup
+    pub fn load(path: &Path) -> Result<Store, Box<dyn std::error::Error>> {
+        if !path.exists() {
+            return Ok(Store::default());
+        }
+        let contents = fs::read_to_string(path)?;
+        serde_json::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+        write_atomic(path, serde_json::to_string_pretty(self)?.as_bytes())?;
+        Ok(())
+    }
+
+    // Add newly fetched entries, replacing any we already had for the same
+    // comment (titles and snippets get edited). Returns how many were new.
+    pub fn merge(&mut self, acks: Vec<Ack>) -> usize {
+        let mut added = 0;
+        for ack in acks {
+            match self.acks.iter_mut().find(|existing| {
+                existing.comment_url == ack.comment_url && existing.mode == ack.mode
+            }) {
+                Some(existing) => *existing = ack,
+                None => {
+                    self.acks.push(ack);
+                    added += 1;
+                }
+            }
+        }
+
+        // Newest first, with the URL as a tie-breaker so the file is stable
+        self.acks.sort_by(|a, b| {
+            b.date
+                .cmp(&a.date)
+                .then_with(|| a.comment_url.cmp(&b.comment_url))
+        });
+        self.updated_at = Some(Utc::now());
+        added
+    }
+
+    // Drop every entry for a comment, e.g. after it was deleted. Returns how
+    // many were removed.
+    pub fn remove_comment(&mut self, comment_url: &str) -> usize {
+        let before = self.acks.len();
+        self.acks.retain(|ack| ack.comment_url != comment_url);
+        before - self.acks.len()
+    }
+
+    // Bring the PR details of stored entries up to date, e.g. after a PR was
+    // renamed or merged. Returns how many entries changed.
+    pub fn update_pr(&mut self, repo: &str, pr: &PullRequest) -> usize {
+        let mut updated = 0;
+        for ack in &mut self.acks {
+            if ack.repo != repo || ack.pr_number != pr.number {
+                continue;
+            }
+            let before = ack.clone();
+            ack.pr_title = pr.title.clone();
+            ack.pr_state = pr.status();
+            ack.pr_merged_at = pr.merged_at;
+            ack.pr_labels = pr.labels.clone();
+            ack.pr_author = pr.user.as_ref().map(|user| user.login.clone());
+            ack.pr_milestone = pr.milestone.as_ref().map(|m| m.title.clone());
+            if *ack != before {
+                updated += 1;
+            }
+        }
+        updated
+    }
+
+    pub fn acks_for(&self, mode: &Mode) -> Vec<Ack> {
+        self.acks
+            .iter()
+            .filter(|ack| &ack.mode == mode)
+            .cloned()
+            .collect()
+    }
+}
@@ -0,0 +1,245 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+use minijinja::{AutoEscape, Environment, Error, Output, State, Value};
+use serde::Serialize;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use crate::escape::{escape_html, safe_url};
+use crate::markdown::render_snippet;
+
+// Built-in templates, used for anything the --templates directory doesn't
+// override. A custom directory only needs the files it changes.
+const DEFAULT_TEMPLATES: &[(&str, &str)] = &[
+    ("layout.html", include_str!("../templates/layout.html")),
+    ("style.css", include_str!("../templates/style.css")),
+    ("index.html", include_str!("../templates/index.html")),
+    ("error.html", include_str!("../templates/error.html")),
+    (
+        "partials/date_group.html",
+        include_str!("../templates/partials/date_group.html"),
+    ),
+    (
+        "partials/ack_entry.html",
+        include_str!("../templates/partials/ack_entry.html"),
+    ),
+    (
+        "partials/archive_nav.html",
+        include_str!("../templates/partials/archive_nav.html"),
+    ),
+    (
+        "partials/search.html",
+        include_str!("../templates/partials/search.html"),
+    ),
+];
+
+// Every value printed into an HTML template goes through `escape_html`.
+// (minijinja's own escaping would also turn each "/" into "&#x2f;", making
+// links in the generated pages hard to read.)
+fn escape_formatter(out: &mut Output, state: &State, value: &Value) -> Result<(), Error> {
+    if let (AutoEscape::Html, false, Some(text)) =
+        (state.auto_escape(), value.is_safe(), value.as_str())
+    {
+        out.write_str(&escape_html(text))?;
+        return Ok(());
+    }
+    minijinja::escape_formatter(out, state, value)
+}
+
+// The faces pages used to load from Google Fonts, served from fonts/ instead.
+// Roboto and Roboto Mono are under the Apache License 2.0, Cormorant Garamond
+// under the SIL Open Font License; their license texts sit next to them.
+#[derive(Serialize)]
+pub struct Font {
+    pub family: &'static str,
+    pub weight: u32,
+    pub path: &'static str,
+}
+
+pub const FONTS: &[Font] = &[
+    Font {
+        family: "Roboto",
+        weight: 100,
+        path: "fonts/Roboto-Thin.woff2",
+    },
+    Font {
+        family: "Roboto",
+        weight: 400,
+        path: "fonts/Roboto-Regular.woff2",
+    },
+    Font {
+        family: "Roboto Mono",
+        weight: 100,
+        path: "fonts/RobotoMono-Thin.woff2",
+    },
+    Font {
+        family: "Roboto Mono",
+        weight: 400,
+        path: "fonts/RobotoMono-Regular.woff2",
+    },
+    Font {
+        family: "Cormorant Garamond",
+        weight: 300,
+        path: "fonts/CormorantGaramond-Light.woff2",
+    },
+    Font {
+        family: "Cormorant Garamond",
+        weight: 400,
+        path: "fonts/CormorantGaramond-Regular.woff2",
+    },
+];
+
+// A file as a data: URL, for pages that must work without anything next to them
+fn data_url(path: &Path) -> std::io::Result<String> {
+    let mime = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("png") => "image/png",
+        Some("svg") => "image/svg+xml",
+        Some("woff2") => "font/woff2",
+        Some("woff") => "font/woff",
+        _ => "application/octet-stream",
+    };
+    Ok(format!(
+        "data:{};base64,{}",
+        mime,
+        BASE64.encode(fs::read(path)?)
+    ))
+}
+
+// Where a PR's page goes, under its repo so PRs with the same number in
+// different repos (or mail threads) get pages of their own, e.g.
+// "pr/bitcoin/bitcoin/123.html". Anything that isn't a plain name is dropped
+// from the repo so the page stays inside pr/.
+pub fn pr_page_path(repo: &str, number: u32) -> String {
+    let mut path = String::from("pr/");
+    for part in repo.split(['/', ':']) {
+        let part: String = part
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || "._-".contains(c) {
+                    c
+                } else {
+                    '-'
+                }
+            })
+            .collect();
+        if !part.is_empty() && part != "." && part != ".." {
+            path.push_str(&part);
+            path.push('/');
+        }
+    }
+    format!("{}{}.html", path, number)
+}
+
+// How dates appear on the generated pages. The data file, index.json and
+// the feed keep UTC timestamps.
+#[derive(Clone)]
+pub struct DateDisplay {
+    pub timezone: Tz,
+    pub format: String,
+}
+
+impl Default for DateDisplay {
+    fn default() -> Self {
+        DateDisplay {
+            timezone: Tz::UTC,
+            format: "%Y-%m-%d".to_string(),
+        }
+    }
+}
+
+impl DateDisplay {
+    // The calendar day `date` falls on in the display timezone
+    pub fn day(&self, date: &DateTime<Utc>) -> NaiveDate {
+        date.with_timezone(&self.timezone).date_naive()
+    }
+
+    pub fn format_day(&self, day: NaiveDate) -> String {
+        day.format(&self.format).to_string()
+    }
+
+    // A point in time to the minute, with the zone's abbreviation
+    pub fn format_time(&self, date: &DateTime<Utc>) -> String {
+        date.with_timezone(&self.timezone)
+            .format(&format!("{} %H:%M %Z", self.format))
+            .to_string()
+    }
+}
+
+pub struct Templates {
+    env: Environment<'static>,
+    pub dates: DateDisplay,
+    // Pages carry their fonts and images inline instead of linking to them
+    pub single_file: bool,
+}
+
+impl Templates {
+    pub fn new(dir: Option<&Path>, dates: DateDisplay, single_file: bool) -> Self {
+        let dir: Option<PathBuf> = dir.map(Path::to_path_buf);
+        let mut env = Environment::new();
+        env.set_formatter(escape_formatter);
+        env.add_filter("safe_url", |url: &str| safe_url(url));
+        // RFC 3339 timestamps, as dates are serialized, to a day
+        let filter_dates = dates.clone();
+        env.add_filter("date", move |timestamp: &str| {
+            DateTime::parse_from_rfc3339(timestamp)
+                .map(|date| filter_dates.format_day(filter_dates.day(&date.with_timezone(&Utc))))
+                .unwrap_or_else(|_| timestamp.to_string())
+        });
+        // A file under images/ or fonts/, relative to the page's `root`
+        env.add_function("asset", move |state: &State, path: &str| {
+            if !single_file {
+                let root = state.lookup("root").map(|root| root.to_string());
+                return Ok(format!("{}{}", root.unwrap_or_default(), path));
+            }
+            data_url(Path::new(path)).map_err(|e| {
+                Error::new(
+                    minijinja::ErrorKind::InvalidOperation,
+                    format!("{}: {}", path, e),
+                )
+            })
+        });
+        // Whether an asset is there to link or inline, so optional files
+        // like the fonts can be left out of a page instead of failing it
+        env.add_function("has_asset", |path: &str| Path::new(path).is_file());
+        env.add_global("fonts", Value::from_serialize(FONTS));
+        env.add_function("pr_page", pr_page_path);
+        env.add_filter("markdown", |text: &str| {
+            Value::from_safe_string(render_snippet(text))
+        });
+        env.set_loader(move |name| {
+            if let Some(dir) = &dir {
+                // Names come from our own templates, but a custom one could
+                // include anything; keep lookups inside the directory
+                if !name.split('/').any(|part| part == "..") {
+                    match fs::read_to_string(dir.join(name)) {
+                        Ok(source) => return Ok(Some(source)),
+                        Err(e) if e.kind() == ErrorKind::NotFound => {}
+                        Err(e) => {
+                            return Err(minijinja::Error::new(
+                                minijinja::ErrorKind::InvalidOperation,
+                                format!("{}: {}", dir.join(name).display(), e),
+                            ))
+                        }
+                    }
+                }
+            }
+            Ok(DEFAULT_TEMPLATES
+                .iter()
+                .find(|(default_name, _)| *default_name == name)
+                .map(|(_, source)| source.to_string()))
+        });
+        Templates {
+            env,
+            dates,
+            single_file,
+        }
+    }
+
+    pub fn render(&self, name: &str, context: Value) -> Result<String, Box<dyn std::error::Error>> {
+        let template = self.env.get_template(name)?;
+        Ok(template.render(context)?)
+    }
+}
@@ -0,0 +1,194 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Component, Path};
+
+use crate::Mode;
+
+// Branding from the `[theme]` table of ackamoto.toml, for running an instance
+// for other projects. Anything left out keeps the ACKamoto/NACKamoto look.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Theme {
+    // The project being reviewed, as in "Bitcoin Core ACKs"
+    pub project: Option<String>,
+    // Markdown shown at the bottom of every page
+    pub footer: Option<String>,
+    pub colors: Option<Colors>,
+    pub dark_colors: Option<Colors>,
+    #[serde(default)]
+    pub ack: SiteTheme,
+    #[serde(default)]
+    pub nack: SiteTheme,
+}
+
+// One of the two sites
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SiteTheme {
+    pub name: Option<String>,
+    // Used instead of the one in CNAME
+    pub domain: Option<String>,
+    // Image paths relative to the working directory, copied into the output
+    pub logo: Option<String>,
+    pub logo_dark: Option<String>,
+    // Replace the link to the other site
+    pub links: Option<Vec<Link>>,
+}
+
+// Values for the stylesheet's color variables (--bg-color and so on)
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Colors {
+    #[serde(rename(serialize = "bg"), skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub border: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub muted: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Link {
+    pub text: String,
+    pub url: String,
+}
+
+impl Theme {
+    fn site(&self, mode: &Mode) -> &SiteTheme {
+        match mode {
+            Mode::Ack => &self.ack,
+            Mode::Nack => &self.nack,
+        }
+    }
+
+    // Checked at startup along with the other render options
+    pub fn validate(&self) -> Result<(), String> {
+        for colors in [&self.colors, &self.dark_colors].into_iter().flatten() {
+            for value in [
+                &colors.background,
+                &colors.text,
+                &colors.border,
+                &colors.muted,
+            ]
+            .into_iter()
+            .flatten()
+            {
+                // Colors go into a stylesheet; keep them to a single value
+                let allowed = |c: char| c.is_ascii_alphanumeric() || "#(),.% ".contains(c);
+                if value.is_empty() || !value.chars().all(allowed) {
+                    return Err(format!("theme color {:?} is not a CSS color", value));
+                }
+            }
+        }
+        for site in [&self.ack, &self.nack] {
+            if let Some(domain) = &site.domain {
+                // Becomes https://<domain> in links, the feed and the sitemap
+                let allowed = |c: char| c.is_ascii_alphanumeric() || "-.:".contains(c);
+                if domain.is_empty() || !domain.chars().all(allowed) {
+                    return Err(format!("theme domain {:?} is not a host name", domain));
+                }
+            }
+            for logo in [&site.logo, &site.logo_dark].into_iter().flatten() {
+                let inside = Path::new(logo)
+                    .components()
+                    .all(|component| matches!(component, Component::Normal(_)));
+                if logo.is_empty() || !inside {
+                    return Err(format!(
+                        "theme logo {:?} must be a relative path inside the working directory",
+                        logo
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Names, links and branding for one of the two sites, as templates see them
+#[derive(Serialize)]
+pub struct Site {
+    #[serde(rename = "type")]
+    pub site_type: &'static str,
+    pub project: String,
+    pub title: String,
+    pub url: String,
+    pub domain: String,
+    pub logo: String,
+    pub logo_dark: String,
+    pub links: Vec<Link>,
+    pub footer: Option<String>,
+    pub colors: Option<Colors>,
+    pub dark_colors: Option<Colors>,
+}
+
+impl Site {
+    pub fn new(mode: &Mode, theme: &Theme) -> Site {
+        let other_mode = match mode {
+            Mode::Ack => Mode::Nack,
+            Mode::Nack => Mode::Ack,
+        };
+        let mut site = Site::without_links(mode, theme);
+        site.links = match &theme.site(mode).links {
+            Some(links) => links.clone(),
+            None => {
+                let other = Site::without_links(&other_mode, theme);
+                vec![Link {
+                    text: format!("Looking for {}s? → {}", other.site_type, other.domain),
+                    url: other.url,
+                }]
+            }
+        };
+        site
+    }
+
+    fn without_links(mode: &Mode, theme: &Theme) -> Site {
+        let (name, site_type, title, cname) = match mode {
+            Mode::Ack => ("ackamoto", "ACK", "ACKamoto", "CNAME"),
+            Mode::Nack => ("nackamoto", "NACK", "NACKamoto", "CNAME.nackamoto"),
+        };
+        let branding = theme.site(mode);
+        // The domain each site is deployed to, as GitHub Pages reads it
+        let domain = branding.domain.clone().unwrap_or_else(|| {
+            fs::read_to_string(cname)
+                .ok()
+                .map(|contents| contents.trim().to_string())
+                .filter(|domain| !domain.is_empty())
+                .unwrap_or_else(|| format!("{}.com", name))
+        });
+        Site {
+            site_type,
+            project: theme
+                .project
+                .clone()
+                .unwrap_or_else(|| "Bitcoin Core".to_string()),
+            title: branding.name.clone().unwrap_or_else(|| title.to_string()),
+            url: format!("https://{}", domain),
+            domain,
+            logo: branding
+                .logo
+                .clone()
+                .unwrap_or_else(|| format!("images/{}-logo.png", name)),
+            // A custom logo without a dark variant is used for both
+            logo_dark: branding
+                .logo_dark
+                .clone()
+                .or_else(|| branding.logo.clone())
+                .unwrap_or_else(|| format!("images/{}-logo-dark.png", name)),
+            links: Vec::new(),
+            footer: theme.footer.clone(),
+            colors: theme.colors.clone(),
+            dark_colors: theme.dark_colors.clone(),
+        }
+    }
+
+    // Absolute URL of a generated page, with index.html as the bare root
+    pub fn page_url(&self, path: &str) -> String {
+        match path {
+            "index.html" => format!("{}/", self.url),
+            _ => format!("{}/{}", self.url, path),
+        }
+    }
+}
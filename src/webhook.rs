@@ -0,0 +1,386 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+
+use crate::github::Review;
+use crate::serve::{read_request, respond};
+use crate::store::Store;
+use crate::{
+    collect_acks, render, Comment, FetchArgs, Label, Milestone, PullRequest, RenderArgs, User,
+};
+
+// GitHub caps webhook payloads at 25 MB
+const MAX_PAYLOAD: usize = 25 * 1024 * 1024;
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(clap::Args, Clone)]
+pub struct WebhookArgs {
+    /// Address to listen on; put it behind a TLS-terminating proxy
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    listen: SocketAddr,
+    /// Secret configured on the GitHub webhook
+    #[arg(long, env = "GITHUB_WEBHOOK_SECRET", hide_env_values = true)]
+    secret: String,
+    #[command(flatten)]
+    pub fetch: FetchArgs,
+    #[command(flatten)]
+    pub render: RenderArgs,
+}
+
+#[derive(Debug, Deserialize)]
+struct Repository {
+    full_name: String,
+}
+
+// Issues and PRs share numbering; only issues with a `pull_request` key are PRs
+#[derive(Debug, Deserialize)]
+struct Issue {
+    number: u32,
+    title: String,
+    html_url: String,
+    state: String,
+    #[serde(default)]
+    draft: bool,
+    #[serde(default)]
+    labels: Vec<Label>,
+    user: Option<User>,
+    milestone: Option<Milestone>,
+    pull_request: Option<IssuePullRequest>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IssuePullRequest {
+    merged_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IssueCommentEvent {
+    action: String,
+    issue: Issue,
+    comment: Comment,
+    repository: Repository,
+}
+
+#[derive(Debug, Deserialize)]
+struct PullRequestReviewEvent {
+    action: String,
+    review: Review,
+    pull_request: PullRequest,
+    repository: Repository,
+}
+
+#[derive(Debug, Deserialize)]
+struct PullRequestEvent {
+    pull_request: PullRequest,
+    repository: Repository,
+}
+
+// What a delivery asks us to do to the stored data
+enum Update {
+    Add(PullRequest, Comment),
+    Remove(String),
+    PrChanged(PullRequest),
+    Ignore(&'static str),
+}
+
+// `signature` is the X-Hub-Signature-256 header: "sha256=" and the hex HMAC
+// of the raw body. The comparison is constant-time.
+fn verify_signature(secret: &str, body: &[u8], signature: &str) -> bool {
+    let Some(hex_digest) = signature.strip_prefix("sha256=") else {
+        return false;
+    };
+    let Ok(digest) = hex::decode(hex_digest) else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(secret.as_bytes()) else {
+        return false;
+    };
+    mac.update(body);
+    mac.verify_slice(&digest).is_ok()
+}
+
+fn parse_event(event: &str, body: &[u8], repo: &str) -> Result<Update, Box<dyn std::error::Error>> {
+    let (payload_repo, update) = match event {
+        "ping" => return Ok(Update::Ignore("ping")),
+        "issue_comment" => {
+            let payload: IssueCommentEvent = serde_json::from_slice(body)?;
+            let issue = payload.issue;
+            let update = match issue.pull_request {
+                None => Update::Ignore("comment on an issue, not a PR"),
+                Some(_) if payload.action == "deleted" => Update::Remove(payload.comment.html_url),
+                Some(pull_request) => {
+                    let pr = PullRequest {
+                        number: issue.number,
+                        title: issue.title,
+                        html_url: issue.html_url,
+                        state: issue.state,
+                        merged_at: pull_request.merged_at,
+                        draft: issue.draft,
+                        labels: issue.labels,
+                        user: issue.user,
+                        milestone: issue.milestone,
+                        // Issue events don't say which commit the PR is at
+                        head: None,
+                    };
+                    Update::Add(pr, payload.comment)
+                }
+            };
+            (payload.repository.full_name, update)
+        }
+        "pull_request_review" => {
+            let payload: PullRequestReviewEvent = serde_json::from_slice(body)?;
+            let update = if payload.action == "dismissed" {
+                Update::Ignore("review dismissed")
+            } else {
+                match payload.review.into_comment() {
+                    Some(comment) => Update::Add(payload.pull_request, comment),
+                    None => Update::Ignore("review not submitted"),
+                }
+            };
+            (payload.repository.full_name, update)
+        }
+        // Keeps titles, state and labels current as PRs are edited and merged
+        "pull_request" => {
+            let payload: PullRequestEvent = serde_json::from_slice(body)?;
+            (
+                payload.repository.full_name,
+                Update::PrChanged(payload.pull_request),
+            )
+        }
+        _ => return Ok(Update::Ignore("unhandled event")),
+    };
+
+    if !payload_repo.eq_ignore_ascii_case(repo) {
+        return Ok(Update::Ignore("different repository"));
+    }
+    Ok(update)
+}
+
+// Apply one verified delivery to the data file and re-render if anything
+// changed. Returns a short description for the log and the response.
+fn apply(
+    args: &WebhookArgs,
+    data: &Path,
+    event: &str,
+    body: &[u8],
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut store = Store::load(data)?;
+
+    let changed = match parse_event(event, body, &args.fetch.repo)? {
+        Update::Add(pr, comment) => {
+            // The same bot and user filters as a full fetch
+            let acks = collect_acks(&args.fetch, vec![(pr, vec![comment.clone()])]);
+            // An edit may have removed the ACK, so drop the old entries first
+            let removed = store.remove_comment(&comment.html_url);
+            let found = acks.len();
+            store.merge(acks);
+            if found == 0 && removed == 0 {
+                return Ok(format!("{}: no ACK or NACK", event));
+            }
+            format!("{}: {} found, {} replaced", event, found, removed)
+        }
+        Update::Remove(comment_url) => match store.remove_comment(&comment_url) {
+            0 => return Ok(format!("{}: nothing stored for deleted comment", event)),
+            removed => format!("{}: {} removed", event, removed),
+        },
+        Update::PrChanged(pr) => match store.update_pr(&args.fetch.repo, &pr) {
+            0 => return Ok(format!("{}: nothing to update for #{}", event, pr.number)),
+            updated => format!("{}: updated {} entries for #{}", event, updated, pr.number),
+        },
+        Update::Ignore(reason) => return Ok(format!("{}: ignored ({})", event, reason)),
+    };
+
+    store.save(data)?;
+    render(&args.render, data)?;
+    Ok(changed)
+}
+
+// `data` is locked while a delivery is applied, so only the reading of
+// requests and the signature checks run side by side
+async fn handle(
+    stream: &mut TcpStream,
+    args: &WebhookArgs,
+    data: &Mutex<PathBuf>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let read = tokio::time::timeout(READ_TIMEOUT, read_request(stream, MAX_PAYLOAD));
+    let request = match read.await {
+        Ok(Ok(Some(request))) => request,
+        Ok(Ok(None)) => return Ok(()),
+        Ok(Err(e)) => return Err(e.into()),
+        Err(_) => return Err("timed out reading the request".into()),
+    };
+
+    if request.method != "POST" {
+        respond(stream, "405 Method Not Allowed", "text/plain", b"", false).await?;
+        return Ok(());
+    }
+
+    let signature = request
+        .headers
+        .get("x-hub-signature-256")
+        .map(String::as_str)
+        .unwrap_or_default();
+    if !verify_signature(&args.secret, &request.body, signature) {
+        eprintln!("Rejected delivery with a missing or invalid signature");
+        respond(
+            stream,
+            "401 Unauthorized",
+            "text/plain",
+            b"Bad signature",
+            false,
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let event = request
+        .headers
+        .get("x-github-event")
+        .map(String::as_str)
+        .unwrap_or_default();
+    let applied = {
+        let data = data.lock().await;
+        apply(args, &data, event, &request.body).map_err(|e| e.to_string())
+    };
+    match applied {
+        Ok(summary) => {
+            println!("{}", summary);
+            respond(stream, "200 OK", "text/plain", summary.as_bytes(), false).await?;
+        }
+        Err(message) => {
+            eprintln!("Failed to process {} delivery: {}", event, message);
+            respond(
+                stream,
+                "400 Bad Request",
+                "text/plain",
+                message.as_bytes(),
+                false,
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+// Receive GitHub webhook deliveries and update the site as soon as a comment
+// or review is posted, instead of waiting for the next scheduled fetch.
+// Connections are read concurrently, but deliveries are applied one at a time
+// so two of them never race on the data file.
+pub async fn run(args: &WebhookArgs, data: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    if args.secret.is_empty() {
+        return Err("--secret (or GITHUB_WEBHOOK_SECRET) must not be empty".into());
+    }
+
+    let listener = TcpListener::bind(args.listen).await?;
+    println!(
+        "Listening for {} webhooks on http://{}/",
+        args.fetch.repo,
+        listener.local_addr()?
+    );
+
+    let args = Arc::new(args.clone());
+    let data = Arc::new(Mutex::new(data.to_path_buf()));
+    loop {
+        let (mut stream, peer) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                eprintln!("Failed to accept connection: {}", e);
+                continue;
+            }
+        };
+        let args = args.clone();
+        let data = data.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle(&mut stream, &args, &data).await {
+                eprintln!("Failed to handle request from {}: {}", peer, e);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The example from GitHub's webhook documentation
+    const SECRET: &str = "It's a Secret to Everybody";
+    const SIGNATURE: &str =
+        "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+
+    #[test]
+    fn checks_signatures() {
+        let body = b"Hello, World!";
+        assert!(verify_signature(SECRET, body, SIGNATURE));
+        assert!(!verify_signature(SECRET, b"Hello, World?", SIGNATURE));
+        assert!(!verify_signature("another secret", body, SIGNATURE));
+        // Missing or malformed headers
+        assert!(!verify_signature(SECRET, body, ""));
+        assert!(!verify_signature(SECRET, body, &SIGNATURE[7..]));
+        assert!(!verify_signature(
+            SECRET,
+            body,
+            &SIGNATURE.replace("sha256", "sha1")
+        ));
+        assert!(!verify_signature(SECRET, body, "sha256=not hex"));
+        assert!(!verify_signature(SECRET, body, &SIGNATURE[..40]));
+    }
+
+    fn issue_comment(action: &str, pull_request: bool, repo: &str) -> Vec<u8> {
+        let mut issue = serde_json::json!({
+            "number": 42,
+            "title": "wallet: fix fee bump",
+            "html_url": "https://github.com/bitcoin/bitcoin/pull/42",
+            "state": "open",
+        });
+        if pull_request {
+            issue["pull_request"] = serde_json::json!({ "merged_at": null });
+        }
+        serde_json::to_vec(&serde_json::json!({
+            "action": action,
+            "issue": issue,
+            "comment": {
+                "body": "ACK abc123",
+                "created_at": "2024-03-05T12:00:00Z",
+                "html_url": "https://github.com/bitcoin/bitcoin/pull/42#issuecomment-1",
+                "user": { "login": "alice", "html_url": "https://github.com/alice" },
+            },
+            "repository": { "full_name": repo },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn parses_comment_events() {
+        let body = issue_comment("created", true, "bitcoin/bitcoin");
+        let update = parse_event("issue_comment", &body, "Bitcoin/Bitcoin").unwrap();
+        assert!(matches!(update, Update::Add(pr, comment)
+            if pr.number == 42 && comment.body == "ACK abc123"));
+
+        let body = issue_comment("deleted", true, "bitcoin/bitcoin");
+        let update = parse_event("issue_comment", &body, "bitcoin/bitcoin").unwrap();
+        assert!(matches!(update, Update::Remove(url) if url.ends_with("#issuecomment-1")));
+
+        let body = issue_comment("created", false, "bitcoin/bitcoin");
+        let update = parse_event("issue_comment", &body, "bitcoin/bitcoin").unwrap();
+        assert!(matches!(
+            update,
+            Update::Ignore("comment on an issue, not a PR")
+        ));
+
+        let body = issue_comment("created", true, "bitcoin/gui");
+        let update = parse_event("issue_comment", &body, "bitcoin/bitcoin").unwrap();
+        assert!(matches!(update, Update::Ignore("different repository")));
+
+        assert!(parse_event("issue_comment", b"{}", "bitcoin/bitcoin").is_err());
+        let update = parse_event("ping", b"{}", "bitcoin/bitcoin").unwrap();
+        assert!(matches!(update, Update::Ignore("ping")));
+    }
+}